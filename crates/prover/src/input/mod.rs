@@ -6,6 +6,7 @@ mod decode;
 pub mod instructions;
 pub mod mem;
 pub mod plain;
+pub mod runnair;
 pub mod vm_import;
 
 pub const N_REGISTERS: usize = 3;
@@ -0,0 +1,217 @@
+use std::collections::BTreeMap;
+
+use runner::export::{public_input, sorted_absolute_data, ExportError};
+use runner::vm::opcode::{ApUpdate, JumpMode, Opcode, Operand, Operation, Register};
+use runner::vm::VM;
+use stwo_prover::core::fields::m31::M31;
+use thiserror::Error;
+
+use super::instructions::{Instructions, VmState};
+use super::mem::{MemConfig, MemoryBuilder};
+use super::vm_import::{MemEntry, TraceEntry};
+use super::{CairoInput, SegmentAddrs};
+
+#[derive(Debug, Error)]
+pub enum RunnairImportError {
+    #[error("Export error: {0}")]
+    Export(#[from] ExportError),
+    #[error("Unknown memory cell: {0}.")]
+    UnknownMemoryCell(u32),
+    #[error("Unknown opcode: {0}.")]
+    UnknownOpcode(M31),
+}
+
+/// Converts a finished runnair run to a prover input, without going through the exported files.
+/// Assumes trace recording was enabled and the VM was relocated.
+pub fn input_from_finished_vm(vm: &VM) -> Result<CairoInput, RunnairImportError> {
+    let public_input = public_input(vm)?;
+    let trace = vm.relocated_trace().ok_or(ExportError::NoTrace)?;
+    let data: BTreeMap<u32, [M31; 4]> = sorted_absolute_data(vm.memory())?
+        .into_iter()
+        .map(|(addr, value)| (addr.0, value))
+        .collect();
+
+    let end_addr = public_input
+        .memory_segments
+        .values()
+        .map(|segment| segment.stop_ptr)
+        .max()
+        .unwrap_or_default();
+    let mem_config = MemConfig::new((1 << 20) - 1, end_addr as u32);
+    let mem = MemoryBuilder::from_iter(
+        mem_config,
+        data.iter().map(|(&addr, value)| MemEntry {
+            addr,
+            val: value.map(|x| x.0),
+        }),
+    );
+
+    let mut instructions = Instructions::default();
+    let mut states = trace.iter().map(|entry| {
+        VmState::from(TraceEntry {
+            ap: entry.ap,
+            fp: entry.fp,
+            pc: entry.pc,
+        })
+    });
+    if let Some(first) = states.next() {
+        instructions.initial_state = first.clone();
+        instructions.final_state = first.clone();
+        push_instruction(&mut instructions, &data, first)?;
+    }
+    for state in states {
+        instructions.final_state = state.clone();
+        push_instruction(&mut instructions, &data, state)?;
+    }
+
+    let public_mem_addresses = public_input
+        .public_memory
+        .iter()
+        .map(|entry| entry.address as u32)
+        .collect();
+
+    Ok(CairoInput {
+        instructions,
+        mem: mem.build(),
+        public_mem_addresses,
+        // Runnair has no range check builtin, so its segment is empty, right after the memory.
+        range_check_builtin: SegmentAddrs {
+            begin_addr: end_addr as u32,
+            end_addr: end_addr as u32,
+        },
+    })
+}
+
+/// Classifies the runnair instruction at `state.pc` into its Stwo opcode.
+fn push_instruction(
+    instructions: &mut Instructions,
+    data: &BTreeMap<u32, [M31; 4]>,
+    state: VmState,
+) -> Result<(), RunnairImportError> {
+    let [op, _, arg1, _] = *data
+        .get(&state.pc)
+        .ok_or(RunnairImportError::UnknownMemoryCell(state.pc))?;
+    let opcode = Opcode::try_from(op).map_err(|_| RunnairImportError::UnknownOpcode(op))?;
+    let fp = |register| usize::from(register == Register::Fp);
+    let appp = usize::from(opcode.ap_update() == ApUpdate::Add1);
+
+    match opcode.operation() {
+        Operation::Ret => instructions.ret.push(state),
+        Operation::AddAp(Operand::Imm) => instructions.add_ap.push(state),
+        Operation::Jmp(JumpMode::Rel, Operand::Imm) => instructions.jmp_rel_imm[appp].push(state),
+        Operation::Jmp(JumpMode::Abs, Operand::Deref(target)) => {
+            instructions.jmp_abs[fp(target) | appp << 1].push(state)
+        }
+        Operation::Call(JumpMode::Rel, Operand::Imm) => instructions.call_rel_imm.push(state),
+        Operation::Call(JumpMode::Abs, Operand::Deref(target)) => {
+            instructions.call_abs[fp(target)].push(state)
+        }
+        Operation::Jnz(Operand::Imm, cond) => {
+            let base = if cond == Register::Fp {
+                state.fp
+            } else {
+                state.ap
+            };
+            let cond_addr = (M31(base) + arg1).0;
+            let condition = data
+                .get(&cond_addr)
+                .ok_or(RunnairImportError::UnknownMemoryCell(cond_addr))?;
+            let taken = condition.iter().any(|limb| limb.0 != 0) as usize;
+            instructions.jnz_imm[fp(cond) | taken << 1 | appp << 2].push(state);
+        }
+        Operation::Assert(_, Operand::Deref(_)) => instructions.mov_mem.push(state),
+        Operation::Assert(_, Operand::DoubleDeref(_)) => instructions.deref.push(state),
+        Operation::Assert(_, Operand::Imm) => instructions.push_imm.push(state),
+        _ => instructions.generic.push(state),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use runner::test_utils::{fibonacci_input, fibonacci_program, fibonacci_vm};
+    use runner::vm::hints::HintRunner;
+
+    use super::*;
+
+    #[test]
+    fn test_input_from_finished_vm() {
+        let program_len = fibonacci_program().instructions.len();
+        let mut vm = fibonacci_vm(5);
+        vm.enable_trace_recording();
+        vm.execute().unwrap();
+        let output_len = vm.output().unwrap().len();
+        vm.relocate();
+        let program_base = vm.relocation_table().unwrap()[&0].0;
+
+        let input = input_from_finished_vm(&vm).unwrap();
+
+        let counts = input.instructions.counts();
+        let n_steps = counts.ret
+            + counts.add_ap
+            + counts.jmp_rel_imm.iter().sum::<usize>()
+            + counts.jmp_abs.iter().sum::<usize>()
+            + counts.call_rel_imm
+            + counts.call_abs.iter().sum::<usize>()
+            + counts.jnz_imm.iter().sum::<usize>()
+            + counts.mov_mem
+            + counts.deref
+            + counts.push_imm
+            + counts.generic;
+        assert_eq!(n_steps, vm.relocated_trace().unwrap().len());
+        assert_eq!(counts.ret, counts.call_rel_imm + 1);
+        // The program and the output.
+        assert_eq!(input.public_mem_addresses.len(), program_len + output_len);
+        assert_eq!(input.instructions.initial_state.pc, program_base);
+        assert!(input.range_check_builtin.addresses().is_empty());
+    }
+
+    #[test]
+    fn test_input_from_finished_vm_is_deterministic() {
+        let mut vm = fibonacci_vm(5);
+        vm.enable_trace_recording();
+        vm.execute().unwrap();
+        vm.relocate();
+
+        let input = input_from_finished_vm(&vm).unwrap();
+        let other = input_from_finished_vm(&vm).unwrap();
+
+        assert_eq!(
+            input.mem.address_to_value_index,
+            other.mem.address_to_value_index
+        );
+        assert_eq!(input.mem.f31_values, other.mem.f31_values);
+        assert_eq!(input.mem.inst_cache, other.mem.inst_cache);
+    }
+
+    #[test]
+    fn test_proof_mode_input() {
+        let program = fibonacci_program();
+        let program_len = program.instructions.len();
+        let input = fibonacci_input(5);
+        let mut vm =
+            VM::create_for_proof_mode(program, "__main__.main", HintRunner::new(input)).unwrap();
+        vm.enable_trace_recording();
+        vm.execute().unwrap();
+        let program_segment_len = vm.memory().segment_size(0);
+        let n_public_execution_cells = vm.public_execution_cells().len();
+        let output_len = vm.output().unwrap().len();
+        vm.relocate();
+        let program_base = vm.relocation_table().unwrap()[&0].0;
+
+        let input = input_from_finished_vm(&vm).unwrap();
+
+        // The run starts at the prologue after the program, and ends in its `jmp rel 0` loop.
+        let prologue_pc = program_base + program_len as u32;
+        assert_eq!(input.instructions.initial_state.pc, prologue_pc);
+        assert_eq!(input.instructions.final_state.pc, prologue_pc + 2);
+        // The program with the prologue, the prologue stack and final output pointer, and the
+        // output.
+        assert_eq!(
+            input.public_mem_addresses.len(),
+            program_segment_len + n_public_execution_cells + output_len
+        );
+        assert!(vm.relocated_trace().unwrap().len().is_power_of_two());
+    }
+}
@@ -0,0 +1,163 @@
+//! Compares the dense segment memory against the sparse layout it replaced, on the memory access
+//! pattern of a long-running program, and measures the steps per second of long Fibonacci runs and
+//! of a `jmp rel 0` loop, which only exercises the instruction dispatch.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use runner::assembler::assemble;
+use runner::memory::relocatable::{MaybeRelocatable, Relocatable};
+use runner::memory::segments::SegmentKind;
+use runner::memory::{MaybeRelocatableValue, Memory};
+use runner::vm::hints::HintRunner;
+use runner::vm::{Input, Program, VM};
+use stwo_prover::core::fields::m31::M31;
+
+const N_CELLS: [u32; 2] = [1 << 16, 1 << 20];
+
+/// The previous layout: an optional enum per relocatable cell, and a hash map once relocated.
+#[derive(Default)]
+struct SparseMemory {
+    relocatable_data: Vec<Vec<Option<MaybeRelocatableValue>>>,
+    absolute_data: HashMap<M31, MaybeRelocatableValue>,
+}
+
+impl SparseMemory {
+    fn insert(
+        &mut self,
+        Relocatable { segment, offset }: Relocatable,
+        value: MaybeRelocatableValue,
+    ) {
+        if segment >= self.relocatable_data.len() {
+            self.relocatable_data.resize(segment + 1, Vec::new());
+        }
+        let cells = &mut self.relocatable_data[segment];
+        let offset = offset.0 as usize;
+        if offset >= cells.len() {
+            cells.resize((offset + 1).max(cells.len() * 2), None);
+        }
+        cells[offset] = Some(value);
+    }
+
+    fn get(&self, Relocatable { segment, offset }: Relocatable) -> Option<MaybeRelocatableValue> {
+        *self.relocatable_data.get(segment)?.get(offset.0 as usize)?
+    }
+
+    fn relocate(&mut self, bases: &[M31]) {
+        for (segment, cells) in self.relocatable_data.iter().enumerate() {
+            for (offset, value) in cells.iter().enumerate() {
+                if let Some(value) = value {
+                    let value = match *value {
+                        MaybeRelocatable::Relocatable(value) => {
+                            (bases[value.segment] + value.offset).into()
+                        }
+                        value => value,
+                    };
+                    let addr = bases[segment] + M31(offset as u32);
+                    self.absolute_data.insert(addr, value);
+                }
+            }
+        }
+        self.relocatable_data.clear();
+    }
+}
+
+/// The value written to the `i`th execution cell: every third cell is a pointer, like the frame
+/// pointers and return addresses pushed by calls.
+fn value(program: Relocatable, execution: Relocatable, i: u32) -> MaybeRelocatableValue {
+    match i % 3 {
+        0 => (execution + M31(i / 2)).into(),
+        1 => (program + M31(i % 16)).into(),
+        _ => M31(i).into(),
+    }
+}
+
+fn memory_benches(c: &mut Criterion) {
+    let mut group = c.benchmark_group("memory");
+    for n_cells in N_CELLS {
+        group.bench_with_input(BenchmarkId::new("dense", n_cells), &n_cells, |b, &n| {
+            b.iter(|| {
+                let mut memory = Memory::default();
+                let program = memory.add_segment(SegmentKind::Program);
+                let execution = memory.add_segment(SegmentKind::Execution);
+                for i in 0..n {
+                    let addr = execution + M31(i);
+                    memory.insert(addr, value(program, execution, i)).unwrap();
+                    black_box(memory.get(addr));
+                }
+                let table = memory.relocation_table();
+                memory.relocate(&table);
+                memory
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("sparse", n_cells), &n_cells, |b, &n| {
+            b.iter(|| {
+                let mut memory = SparseMemory::default();
+                let program = Relocatable::from((0, 0));
+                let execution = Relocatable::from((1, 0));
+                for i in 0..n {
+                    let addr = execution + M31(i);
+                    memory.insert(addr, value(program, execution, i));
+                    black_box(memory.get(addr));
+                }
+                memory.relocate(&[M31(1), M31(17)]);
+                memory
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Runs `fib(1, 1, n)` to completion and relocates the VM.
+fn run_fibonacci(program: &Program, n: u32) -> VM {
+    let args = [M31(1), M31(1), M31(n)].map(MaybeRelocatableValue::from);
+    let hint_runner = HintRunner::new(Input::default());
+    let mut vm =
+        VM::create_for_entry_point(program.clone(), "__main__.fib", &args, hint_runner).unwrap();
+    vm.execute().unwrap();
+    vm.relocate();
+    vm
+}
+
+fn fibonacci_benches(c: &mut Criterion) {
+    let program_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("data")
+        .join("fibonacci_compiled.json");
+    let program = Program::from_compiled_file(program_path).unwrap();
+
+    let mut group = c.benchmark_group("fibonacci");
+    group.sample_size(10);
+    for n in [10_000, 100_000] {
+        let n_steps = run_fibonacci(&program, n).n_steps();
+        group.throughput(Throughput::Elements(n_steps as u64));
+        group.bench_with_input(BenchmarkId::new("run_and_relocate", n), &n, |b, &n| {
+            b.iter(|| run_fibonacci(&program, n))
+        });
+    }
+    group.finish();
+}
+
+fn dispatch_benches(c: &mut Criterion) {
+    let program = assemble("jmp rel 0").unwrap();
+
+    let mut group = c.benchmark_group("dispatch");
+    group.sample_size(10);
+    let n_steps = 1_000_000;
+    group.throughput(Throughput::Elements(n_steps as u64));
+    group.bench_function(BenchmarkId::new("jmp_rel_0", n_steps), |b| {
+        b.iter(|| {
+            let hint_runner = HintRunner::new(Input::default());
+            let mut vm = VM::create_for_main_entry_point(program.clone(), hint_runner).unwrap();
+            vm.set_max_steps(n_steps);
+            // The loop never ends, so the run stops with a step limit error.
+            vm.execute().unwrap_err();
+            vm
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, memory_benches, fibonacci_benches, dispatch_benches);
+criterion_main!(benches);
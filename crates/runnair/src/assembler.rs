@@ -0,0 +1,607 @@
+//! An assembler for the syntax printed by the disassembler.
+//!
+//! Each line is one of:
+//! * `%builtins output`: the builtins of the program.
+//! * `name:`: a label at the pc of the next instruction.
+//! * `%{ code %}`: a hint run before the next instruction. Hints of several lines start with a `%{`
+//!   line and end with a `%}` line, and their code is dedented.
+//! * An instruction, e.g. `[ap+0] = [fp-3] + 2, ap++`, `jmp rel loop if [ap-1] != 0` or `ret`,
+//!   optionally preceded by its pc and mnemonic as printed by the disassembler.
+//!
+//! Comments start with `//`, except inside hints. Labels may be used wherever an immediate is
+//! expected, and are resolved relative to the instruction in relative jumps and calls.
+
+use std::collections::HashMap;
+
+use stwo_prover::core::fields::m31::{M31, P};
+use thiserror::Error;
+
+use crate::utils::u32_from_usize;
+use crate::vm::hints::Hints;
+use crate::vm::opcode::{JumpMode, Opcode, Operand, Operation, Register};
+use crate::vm::{Instruction, Program};
+
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum AssemblerError {
+    #[error("Line {line}: invalid syntax: {text}.")]
+    InvalidSyntax { line: usize, text: String },
+    #[error("Line {line}: unknown builtin {name}.")]
+    UnknownBuiltin { line: usize, name: String },
+    #[error("Line {line}: duplicate label {label}.")]
+    DuplicateLabel { line: usize, label: String },
+    #[error("Line {line}: unknown label {label}.")]
+    UnknownLabel { line: usize, label: String },
+    #[error("Line {line}: value {value} is out of range.")]
+    OutOfRange { line: usize, value: i64 },
+    #[error("Line {line}: there is no opcode for {text}.")]
+    UnsupportedInstruction { line: usize, text: String },
+    #[error("Line {line}: the mnemonic {mnemonic} does not match the instruction {name}.")]
+    MnemonicMismatch {
+        line: usize,
+        mnemonic: String,
+        name: String,
+    },
+    #[error("Line {line}: a hint must be followed by an instruction.")]
+    DanglingHint { line: usize },
+    #[error("Line {line}: the hint is never closed by a %}} line.")]
+    UnclosedHint { line: usize },
+}
+
+/// An immediate value, or a label to be resolved to one.
+#[derive(Debug)]
+enum Immediate<'a> {
+    Value(i64),
+    Label(&'a str),
+}
+
+/// A memory cell relative to a register, e.g. `[fp-3]`.
+type Cell = (Register, i64);
+
+/// A parsed operand, with its immediate and offsets.
+#[derive(Debug)]
+enum ParsedOperand<'a> {
+    Imm(Immediate<'a>),
+    Deref(Cell),
+    DoubleDeref(Cell, i64),
+    AddImm(Cell, Immediate<'a>),
+    Add(Cell, Cell),
+    MulImm(Cell, Immediate<'a>),
+    Mul(Cell, Cell),
+}
+
+impl<'a> ParsedOperand<'a> {
+    /// The operand, and its arguments in the layout of `vm::operand`.
+    fn encode(self) -> (Operand, Vec<Immediate<'a>>) {
+        use Immediate::Value;
+        match self {
+            Self::Imm(imm) => (Operand::Imm, vec![imm]),
+            Self::Deref((base, offset)) => (Operand::Deref(base), vec![Value(offset)]),
+            Self::DoubleDeref((base, offset), inner_offset) => (
+                Operand::DoubleDeref(base),
+                vec![Value(offset), Value(inner_offset)],
+            ),
+            Self::AddImm((base, offset), imm) => (Operand::AddImm(base), vec![imm, Value(offset)]),
+            Self::Add((lhs, lhs_offset), (rhs, rhs_offset)) => (
+                Operand::Add(lhs, rhs),
+                vec![Value(lhs_offset), Value(rhs_offset)],
+            ),
+            Self::MulImm((base, offset), imm) => (Operand::MulImm(base), vec![imm, Value(offset)]),
+            Self::Mul((lhs, lhs_offset), (rhs, rhs_offset)) => (
+                Operand::Mul(lhs, rhs),
+                vec![Value(lhs_offset), Value(rhs_offset)],
+            ),
+        }
+    }
+}
+
+/// A line of an instruction, with the pc of the instruction.
+struct InstructionLine<'a> {
+    line: usize,
+    pc: usize,
+    text: &'a str,
+}
+
+/// Assembles a program from its text.
+pub fn assemble(text: &str) -> Result<Program, AssemblerError> {
+    let mut instruction_lines = vec![];
+    let mut hints = Hints::new();
+    let mut builtins = vec![];
+    let mut identifiers = HashMap::new();
+    // The hints before the next instruction, with the line of the first one.
+    let mut pending_hints: Option<(usize, Vec<String>)> = None;
+    // The code lines of the hint of several lines being read, with the line of its `%{`.
+    let mut hint_block: Option<(usize, Vec<&str>)> = None;
+
+    for (index, line_text) in text.lines().enumerate() {
+        let line = index + 1;
+        let pc = instruction_lines.len();
+
+        if let Some((start_line, code_lines)) = &mut hint_block {
+            if line_text.trim() == "%}" {
+                pending_hints
+                    .get_or_insert_with(|| (*start_line, vec![]))
+                    .1
+                    .push(dedent(code_lines));
+                hint_block = None;
+            } else {
+                code_lines.push(line_text);
+            }
+            continue;
+        }
+        if line_text.trim() == "%{" {
+            hint_block = Some((line, vec![]));
+            continue;
+        }
+        // Hints are read before comments are stripped, as their code may hold `//`, e.g. for
+        // floor division.
+        if let Some(hint) = line_text.trim().strip_prefix("%{") {
+            let (code, comment) = hint
+                .split_once("%}")
+                .ok_or_else(|| invalid_syntax(line, line_text.trim()))?;
+            let comment = comment.trim();
+            if !comment.is_empty() && !comment.starts_with("//") {
+                return Err(invalid_syntax(line, line_text.trim()));
+            }
+            pending_hints
+                .get_or_insert_with(|| (line, vec![]))
+                .1
+                .push(code.trim().to_string());
+            continue;
+        }
+
+        let line_text = line_text.split("//").next().unwrap().trim();
+        if line_text.is_empty() {
+            continue;
+        } else if let Some(names) = line_text.strip_prefix("%builtins") {
+            for name in names.split_whitespace() {
+                let builtin = serde_json::from_value(name.into()).map_err(|_| {
+                    AssemblerError::UnknownBuiltin {
+                        line,
+                        name: name.to_string(),
+                    }
+                })?;
+                builtins.push(builtin);
+            }
+        } else if let Some(label) = line_text.strip_suffix(':').filter(|label| is_label(label)) {
+            if identifiers.insert(label.to_string(), pc).is_some() {
+                return Err(AssemblerError::DuplicateLabel {
+                    line,
+                    label: label.to_string(),
+                });
+            }
+        } else {
+            if let Some((_, codes)) = pending_hints.take() {
+                hints.resize(pc, vec![]);
+                hints.push(codes);
+            }
+            instruction_lines.push(InstructionLine {
+                line,
+                pc,
+                text: line_text,
+            });
+        }
+    }
+    if let Some((line, _)) = hint_block {
+        return Err(AssemblerError::UnclosedHint { line });
+    }
+    if let Some((line, _)) = pending_hints {
+        return Err(AssemblerError::DanglingHint { line });
+    }
+
+    let instructions = instruction_lines
+        .iter()
+        .map(|instruction_line| assemble_instruction(instruction_line, &identifiers))
+        .collect::<Result<_, _>>()?;
+
+    Ok(Program {
+        instructions,
+        hints,
+        builtins,
+        identifiers,
+        return_sizes: HashMap::new(),
+        instruction_locations: HashMap::new(),
+    })
+}
+
+fn assemble_instruction(
+    instruction_line: &InstructionLine<'_>,
+    labels: &HashMap<String, usize>,
+) -> Result<Instruction, AssemblerError> {
+    let InstructionLine { line, pc, text } = *instruction_line;
+    let invalid = || invalid_syntax(line, text);
+
+    // Skip the pc printed by the disassembler.
+    let text = match text.split_once(':') {
+        Some((pc, rest)) if pc.trim().parse::<usize>().is_ok() => rest.trim(),
+        _ => text,
+    };
+    let (text, increments_ap) = match text.strip_suffix("ap++") {
+        Some(rest) => (rest.trim_end().strip_suffix(',').ok_or_else(invalid)?, true),
+        None => (text, false),
+    };
+    let (mnemonic, text) = match text.split_once(char::is_whitespace) {
+        Some((mnemonic, rest))
+            if Opcode::ALL
+                .iter()
+                .any(|opcode| opcode.mnemonic() == mnemonic) =>
+        {
+            (Some(mnemonic), rest)
+        }
+        _ => (None, text),
+    };
+    let text = text.trim();
+    let operand = |text| parse_operand(text).ok_or_else(invalid);
+    let parse_mode = |mode| match mode {
+        "abs" => Ok(JumpMode::Abs),
+        "rel" => Ok(JumpMode::Rel),
+        _ => Err(invalid()),
+    };
+
+    let (operation, args) = if text == "ret" {
+        (Operation::Ret, vec![])
+    } else if let Some(summand) = text.strip_prefix("ap +=") {
+        let (summand, args) = operand(summand)?.encode();
+        (Operation::AddAp(summand), args)
+    } else if let Some(rest) = text.strip_prefix("call ") {
+        let (mode, target) = rest.trim().split_once(' ').ok_or_else(invalid)?;
+        let (target, args) = operand(target)?.encode();
+        (Operation::Call(parse_mode(mode)?, target), args)
+    } else if let Some(rest) = text.strip_prefix("jmp ") {
+        let (mode, target) = rest.trim().split_once(' ').ok_or_else(invalid)?;
+        let mode = parse_mode(mode)?;
+        match target.split_once(" if ") {
+            Some((target, condition)) if mode == JumpMode::Rel => {
+                let (cond, cond_offset) = condition
+                    .trim()
+                    .strip_suffix("!= 0")
+                    .and_then(|condition| parse_cell(condition.trim()))
+                    .ok_or_else(invalid)?;
+                let (target, mut args) = operand(target)?.encode();
+                args.push(Immediate::Value(cond_offset));
+                (Operation::Jnz(target, cond), args)
+            }
+            Some(_) => return Err(invalid()),
+            None => {
+                let (target, args) = operand(target)?.encode();
+                (Operation::Jmp(mode, target), args)
+            }
+        }
+    } else {
+        let (dest, value) = text.split_once(" = ").ok_or_else(invalid)?;
+        let (dest, offset) = parse_cell(dest.trim()).ok_or_else(invalid)?;
+        let (value, mut args) = operand(value)?.encode();
+        args.insert(0, Immediate::Value(offset));
+        (Operation::Assert(dest, value), args)
+    };
+
+    let opcode = Opcode::from_operation(operation, increments_ap).ok_or_else(|| {
+        AssemblerError::UnsupportedInstruction {
+            line,
+            text: instruction_line.text.to_string(),
+        }
+    })?;
+    if let Some(mnemonic) = mnemonic.filter(|mnemonic| *mnemonic != opcode.mnemonic()) {
+        return Err(AssemblerError::MnemonicMismatch {
+            line,
+            mnemonic: mnemonic.to_string(),
+            name: opcode.mnemonic().to_string(),
+        });
+    }
+    let relative = matches!(
+        operation,
+        Operation::Call(JumpMode::Rel, _) | Operation::Jmp(JumpMode::Rel, _) | Operation::Jnz(..)
+    );
+
+    let mut encoded_args = [M31(0); 3];
+    for (encoded_arg, arg) in encoded_args.iter_mut().zip(args) {
+        let value = match arg {
+            Immediate::Value(value) => value,
+            Immediate::Label(label) => {
+                let target = *labels
+                    .get(label)
+                    .ok_or_else(|| AssemblerError::UnknownLabel {
+                        line,
+                        label: label.to_string(),
+                    })?;
+                let target = i64::from(u32_from_usize(target));
+                if relative {
+                    target - i64::from(u32_from_usize(pc))
+                } else {
+                    target
+                }
+            }
+        };
+        *encoded_arg = encode_value(value).ok_or(AssemblerError::OutOfRange { line, value })?;
+    }
+
+    Ok(Instruction {
+        op: opcode.into(),
+        args: encoded_args,
+    })
+}
+
+/// Joins the lines of a hint, without the indentation they all share. Blank lines are kept empty.
+fn dedent(lines: &[&str]) -> String {
+    let indent = lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    let lines: Vec<_> = lines
+        .iter()
+        .map(|line| line.get(indent..).unwrap_or_default().trim_end())
+        .collect();
+
+    lines.join("\n").trim_matches('\n').to_string()
+}
+
+fn invalid_syntax(line: usize, text: &str) -> AssemblerError {
+    AssemblerError::InvalidSyntax {
+        line,
+        text: text.to_string(),
+    }
+}
+
+/// Encodes a signed value as a field element, e.g. `-3` as `0x7ffffffc`.
+fn encode_value(value: i64) -> Option<M31> {
+    let p = i64::from(P);
+    if value <= -p || value >= p {
+        return None;
+    }
+    Some(M31(u32::try_from(value.rem_euclid(p)).unwrap()))
+}
+
+fn is_label(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && text
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+fn parse_immediate(text: &str) -> Option<Immediate<'_>> {
+    if is_label(text) {
+        return Some(Immediate::Label(text));
+    }
+    let (sign, digits) = match text.strip_prefix('-') {
+        Some(digits) => (-1, digits),
+        None => (1, text),
+    };
+    let value = match digits.strip_prefix("0x") {
+        Some(hex) => i64::from_str_radix(hex, 16).ok()?,
+        None => digits.parse().ok()?,
+    };
+    Some(Immediate::Value(sign * value))
+}
+
+/// Parses a memory cell relative to a register, e.g. `[fp-3]` or `[ap]`. `text` must not contain
+/// whitespace.
+fn parse_cell(text: &str) -> Option<Cell> {
+    let inner = text.strip_prefix('[')?.strip_suffix(']')?;
+    let base = match inner.get(..2)? {
+        "ap" => Register::Ap,
+        "fp" => Register::Fp,
+        _ => return None,
+    };
+    let offset = match &inner[2..] {
+        "" => 0,
+        offset => parse_offset(offset)?,
+    };
+    Some((base, offset))
+}
+
+/// Parses a signed offset, e.g. `+1` or `-3`.
+fn parse_offset(text: &str) -> Option<i64> {
+    match text.strip_prefix('+') {
+        Some(offset) => offset.parse().ok(),
+        None if text.starts_with('-') => text.parse().ok(),
+        None => None,
+    }
+}
+
+fn parse_operand(text: &str) -> Option<ParsedOperand<'_>> {
+    let text = text.trim();
+    if text.contains(char::is_whitespace) {
+        // Binary operations are separated by whitespace, e.g. `[ap-1] * [fp+2]`.
+        let (lhs, rest) = text.split_once(char::is_whitespace)?;
+        let (op, rhs) = rest.trim_start().split_once(char::is_whitespace)?;
+        let lhs = parse_cell(lhs)?;
+        let rhs = rhs.trim();
+        return Some(match (op, parse_cell(rhs)) {
+            ("+", Some(rhs)) => ParsedOperand::Add(lhs, rhs),
+            ("+", None) => ParsedOperand::AddImm(lhs, parse_immediate(rhs)?),
+            ("*", Some(rhs)) => ParsedOperand::Mul(lhs, rhs),
+            ("*", None) => ParsedOperand::MulImm(lhs, parse_immediate(rhs)?),
+            _ => return None,
+        });
+    }
+    if let Some(inner) = text.strip_prefix("[[") {
+        let (cell, offset) = inner.strip_suffix(']')?.split_once(']')?;
+        let cell = parse_cell(&format!("[{cell}]"))?;
+        let offset = match offset {
+            "" => 0,
+            offset => parse_offset(offset)?,
+        };
+        return Some(ParsedOperand::DoubleDeref(cell, offset));
+    }
+    match parse_cell(text) {
+        Some(cell) => Some(ParsedOperand::Deref(cell)),
+        None => Some(ParsedOperand::Imm(parse_immediate(text)?)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disassembler::disassemble;
+    use crate::test_utils::{fibonacci_input, fibonacci_program};
+    use crate::vm::builtins::Builtin;
+    use crate::vm::hints::HintRunner;
+    use crate::vm::VM;
+
+    #[test]
+    fn test_assemble_instructions() {
+        let text = "
+            %builtins output
+            main:
+                ap += 1
+                %{ memory[ap] = 7 %}
+                [fp+0] = [[fp-3]+0]
+                [ap] = 1, ap++   // A comment.
+                call rel fib
+                [ap+0] = [ap-3] + [ap-2], ap++
+                [ap+0] = [fp-3] + -1, ap++
+                jmp abs [fp-1]
+            fib:
+                jmp rel fib if [fp-3] != 0
+                jmp rel -1, ap++
+                ret
+        ";
+
+        let program = assemble(text).unwrap();
+
+        let expected = [
+            [10, 1, 0, 0],
+            [69, 0, 0x7ffffffc, 0],
+            [38, 0, 1, 0],
+            [90, 4, 0, 0],
+            [18, 0, 0x7ffffffc, 0x7ffffffd],
+            [28, 0, 0x7ffffffe, 0x7ffffffc],
+            [105, 0x7ffffffe, 0, 0],
+            [169, 0, 0x7ffffffc, 0],
+            [146, 0x7ffffffe, 0, 0],
+            [171, 0, 0, 0],
+        ];
+        assert_eq!(program.instructions, expected.map(Instruction::from));
+        assert_eq!(program.hints, [vec![], vec!["memory[ap] = 7".to_string()]]);
+        assert_eq!(program.builtins, [Builtin::Output]);
+        assert_eq!(program.identifiers["main"], 0);
+        assert_eq!(program.identifiers["fib"], 7);
+    }
+
+    #[test]
+    fn test_multiple_hints() {
+        let program = assemble("%{ a %}\n%{ b %}\nret").unwrap();
+
+        assert_eq!(program.hints, [vec!["a".to_string(), "b".to_string()]]);
+        let disassembled = disassemble(&program).unwrap();
+        assert_eq!(assemble(&disassembled).unwrap().hints, program.hints);
+    }
+
+    #[test]
+    fn test_multi_line_hint_round_trip() {
+        let code = "if a:\n    b = a // 2  # Floor division.\n\nc = b";
+        let program = Program {
+            hints: vec![vec![code.to_string(), "d = c".to_string()]],
+            ..Program::from_instructions(&[[171, 0, 0, 0]])
+        };
+
+        let disassembled = disassemble(&program).unwrap();
+
+        let lines = disassembled.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            [
+                "      %{",
+                "          if a:",
+                "              b = a // 2  # Floor division.",
+                "",
+                "          c = b",
+                "      %}",
+                "      %{ d = c %}",
+                "   0: ret",
+            ]
+        );
+        assert_eq!(assemble(&disassembled).unwrap().hints, program.hints);
+    }
+
+    #[test]
+    fn test_hint_with_floor_division() {
+        let program = assemble("%{ x = a // 2 %}  // A comment.\nret").unwrap();
+
+        assert_eq!(program.hints, [vec!["x = a // 2".to_string()]]);
+    }
+
+    #[test]
+    fn test_disassembly_round_trip() {
+        let program = fibonacci_program();
+
+        let assembled = assemble(&disassemble(&program).unwrap()).unwrap();
+
+        assert_eq!(assembled.instructions, program.instructions);
+        assert_eq!(assembled.hints, program.hints);
+        assert_eq!(assembled.builtins, program.builtins);
+        assert_eq!(assembled.identifiers, program.identifiers);
+    }
+
+    #[test]
+    fn test_compiled_json_round_trip() {
+        let text = "
+            %builtins output
+            %{ memory[state.fp] = input['fibonacci_claim_index']; %}
+            [fp+0] = [fp+0]
+            [ap] = [fp], ap++
+            ret
+        ";
+        let program = assemble(text).unwrap();
+
+        let json = serde_json::to_value(&program).unwrap();
+        let loaded: Program = serde_json::from_value(json).unwrap();
+
+        assert_eq!(loaded.instructions, program.instructions);
+        assert_eq!(loaded.hints, program.hints);
+        let input = fibonacci_input(5);
+        let mut vm = VM::create_for_main_entry_point(loaded, HintRunner::new(input)).unwrap();
+        vm.execute().unwrap();
+    }
+
+    #[test]
+    fn test_assembler_errors() {
+        let error = |text| assemble(text).unwrap_err();
+
+        assert_eq!(
+            error("[ap] = [fp] +"),
+            AssemblerError::InvalidSyntax {
+                line: 1,
+                text: "[ap] = [fp] +".to_string()
+            }
+        );
+        assert_eq!(
+            error("ret\njmp rel end"),
+            AssemblerError::UnknownLabel {
+                line: 2,
+                label: "end".to_string()
+            }
+        );
+        assert_eq!(
+            error("ap += 1, ap++"),
+            AssemblerError::UnsupportedInstruction {
+                line: 1,
+                text: "ap += 1, ap++".to_string()
+            }
+        );
+        assert_eq!(
+            error("jmp_abs_imm jmp rel 1"),
+            AssemblerError::MnemonicMismatch {
+                line: 1,
+                mnemonic: "jmp_abs_imm".to_string(),
+                name: "jmp_rel_imm".to_string()
+            }
+        );
+        assert_eq!(
+            error("[ap] = 0x80000000"),
+            AssemblerError::OutOfRange {
+                line: 1,
+                value: 0x80000000
+            }
+        );
+        assert_eq!(
+            error("ret\n%{ a %}"),
+            AssemblerError::DanglingHint { line: 2 }
+        );
+        assert_eq!(
+            error("%{\na\nret"),
+            AssemblerError::UnclosedHint { line: 1 }
+        );
+    }
+}
@@ -0,0 +1,215 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use stwo_prover::core::fields::m31::{M31, P};
+use thiserror::Error;
+
+use crate::vm::opcode::{ApUpdate, JumpMode, Opcode, Operand, Operation, Register};
+use crate::vm::{Instruction, Program};
+
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum DisassemblerError {
+    #[error("Unknown opcode {opcode} at pc {pc}.")]
+    UnknownOpcode { pc: usize, opcode: M31 },
+}
+
+/// Decodes a field element as a signed value, e.g. `0x7ffffffc` as `-3`.
+pub fn signed(value: M31) -> i64 {
+    let value = i64::from(value.0);
+    if value > i64::from(P / 2) {
+        value - i64::from(P)
+    } else {
+        value
+    }
+}
+
+/// A memory cell relative to a register, e.g. `[fp-3]`.
+fn cell(base: Register, offset: M31) -> String {
+    format!("[{}{:+}]", base.name(), signed(offset))
+}
+
+/// Renders an operand from its arguments, e.g. `AddImm(Fp)` with the arguments `[2, -3]` is
+/// `[fp-3] + 2`.
+fn operand(operand: Operand, args: &[M31]) -> String {
+    match operand {
+        Operand::Imm => signed(args[0]).to_string(),
+        Operand::Deref(base) => cell(base, args[0]),
+        Operand::DoubleDeref(base) => format!("[{}{:+}]", cell(base, args[0]), signed(args[1])),
+        Operand::Add(lhs, rhs) => format!("{} + {}", cell(lhs, args[0]), cell(rhs, args[1])),
+        Operand::AddImm(base) => format!("{} + {}", cell(base, args[1]), signed(args[0])),
+        Operand::Mul(lhs, rhs) => format!("{} * {}", cell(lhs, args[0]), cell(rhs, args[1])),
+        Operand::MulImm(base) => format!("{} * {}", cell(base, args[1]), signed(args[0])),
+    }
+}
+
+fn jump_mode(mode: JumpMode) -> &'static str {
+    match mode {
+        JumpMode::Abs => "abs",
+        JumpMode::Rel => "rel",
+    }
+}
+
+/// Disassembles a single instruction, e.g. `[28, 0, 2, 0x7ffffffc]` is
+/// `assert_ap_add_imm_fp [ap+0] = [fp-3] + 2, ap++`.
+pub fn disassemble_instruction(instruction: Instruction) -> Option<String> {
+    let Instruction { op, args } = instruction;
+    let opcode = Opcode::try_from(op).ok()?;
+
+    let operation = match opcode.operation() {
+        Operation::AddAp(summand) => format!("ap += {}", operand(summand, &args)),
+        Operation::Assert(dest, value) => {
+            format!("{} = {}", cell(dest, args[0]), operand(value, &args[1..]))
+        }
+        Operation::Call(mode, target) => {
+            format!("call {} {}", jump_mode(mode), operand(target, &args))
+        }
+        Operation::Jmp(mode, target) => {
+            format!("jmp {} {}", jump_mode(mode), operand(target, &args))
+        }
+        Operation::Jnz(target, cond) => format!(
+            "jmp rel {} if {} != 0",
+            operand(target, &args),
+            cell(cond, args[1])
+        ),
+        Operation::Ret => return Some(opcode.mnemonic().to_string()),
+    };
+
+    let ap_update = match opcode.ap_update() {
+        ApUpdate::Add1 => ", ap++",
+        _ => "",
+    };
+    Some(format!("{} {operation}{ap_update}", opcode.mnemonic()))
+}
+
+/// Disassembles a program, one instruction per line, preceded by its labels and hint, in the
+/// syntax read by the assembler.
+pub fn disassemble(program: &Program) -> Result<String, DisassemblerError> {
+    let mut labels = BTreeMap::<usize, Vec<&str>>::new();
+    for (name, &pc) in &program.identifiers {
+        labels.entry(pc).or_default().push(name);
+    }
+
+    let mut text = String::new();
+    if !program.builtins.is_empty() {
+        let names = program.builtins.iter().map(|builtin| builtin.name());
+        writeln!(text, "%builtins {}", names.collect::<Vec<_>>().join(" ")).unwrap();
+    }
+    for (pc, &instruction) in program.instructions.iter().enumerate() {
+        if let Some(names) = labels.get_mut(&pc) {
+            names.sort_unstable();
+            for name in names {
+                writeln!(text, "{name}:").unwrap();
+            }
+        }
+        for code in program.hints.get(pc).into_iter().flatten() {
+            if code.contains('\n') {
+                // One code line per line, between `%{` and `%}` lines, as the assembler reads them.
+                writeln!(text, "      %{{").unwrap();
+                for code_line in code.lines() {
+                    if code_line.trim().is_empty() {
+                        writeln!(text).unwrap();
+                    } else {
+                        writeln!(text, "          {code_line}").unwrap();
+                    }
+                }
+                writeln!(text, "      %}}").unwrap();
+            } else {
+                writeln!(text, "      %{{ {code} %}}").unwrap();
+            }
+        }
+        let line =
+            disassemble_instruction(instruction).ok_or(DisassemblerError::UnknownOpcode {
+                pc,
+                opcode: instruction.op,
+            })?;
+        writeln!(text, "{pc:>4}: {line}").unwrap();
+    }
+
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::fibonacci_program;
+
+    #[test]
+    fn test_disassemble_instruction() {
+        let cases = [
+            ([10, 1, 0, 0], "addap_imm ap += 1"),
+            (
+                [69, 0, 0x7ffffffc, 0],
+                "assert_fp_double_deref_fp [fp+0] = [[fp-3]+0]",
+            ),
+            ([38, 0, 1, 0], "assert_ap_imm [ap+0] = 1, ap++"),
+            (
+                [28, 0, 2, 0x7ffffffc],
+                "assert_ap_add_imm_fp [ap+0] = [fp-3] + 2, ap++",
+            ),
+            (
+                [46, 0, 0x7ffffffa, 0x7ffffffa],
+                "assert_ap_mul_fp_fp [ap+0] = [fp-5] * [fp-5], ap++",
+            ),
+            ([90, 4, 0, 0], "call_rel_imm call rel 4"),
+            ([85, 0x7fffffff - 2, 0, 0], "call_abs_ap call abs [ap-2]"),
+            ([90, 0x7ffffff7, 0, 0], "call_rel_imm call rel -8"),
+            (
+                [169, 3, 0x7ffffffc, 0],
+                "jnz_imm_fp jmp rel 3 if [fp-3] != 0",
+            ),
+            (
+                [160, 1, 2, 0],
+                "jnz_ap_ap jmp rel [ap+1] if [ap+2] != 0, ap++",
+            ),
+            (
+                [105, 0x7fffffff - 1, 0, 0],
+                "jmp_abs_deref_fp jmp abs [fp-1]",
+            ),
+            ([145, 0, 0, 0], "jmp_rel_imm jmp rel 0"),
+            ([171, 0, 0, 0], "ret"),
+        ];
+
+        for (instruction, expected) in cases {
+            assert_eq!(
+                disassemble_instruction(Instruction::from(instruction)).as_deref(),
+                Some(expected)
+            );
+        }
+        assert_eq!(
+            disassemble_instruction(Instruction::from([172, 0, 0, 0])),
+            None
+        );
+    }
+
+    #[test]
+    fn test_disassemble_fibonacci() {
+        let program = fibonacci_program();
+
+        let text = disassemble(&program).unwrap();
+
+        let lines = text.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), program.instructions.len() + 4);
+        assert_eq!(lines[0], "%builtins output");
+        assert_eq!(lines[1], "__main__.main:");
+        assert_eq!(lines[2], "   0: addap_imm ap += 1");
+        assert_eq!(
+            lines[3],
+            "      %{ memory[state.fp] = input['fibonacci_claim_index']; %}"
+        );
+        assert_eq!(lines[12], "__main__.fib:");
+        assert_eq!(lines[13], "   9: jnz_imm_fp jmp rel 3 if [fp-3] != 0");
+    }
+
+    #[test]
+    fn test_disassemble_unknown_opcode() {
+        let program = Program::from_instructions(&[[171, 0, 0, 0], [200, 0, 0, 0]]);
+
+        assert_eq!(
+            disassemble(&program),
+            Err(DisassemblerError::UnknownOpcode {
+                pc: 1,
+                opcode: M31(200)
+            })
+        );
+    }
+}
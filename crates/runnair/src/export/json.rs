@@ -0,0 +1,68 @@
+use std::collections::BTreeMap;
+
+use serde::{Serialize, Serializer};
+use stwo_prover::core::fields::qm31::QM31;
+
+// Note: These structs must be kept in sync with the prover's `vm_import` JSON inputs.
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PublicInput {
+    pub layout: String,
+    pub rc_min: u64,
+    pub rc_max: u64,
+    pub n_steps: u64,
+    pub memory_segments: BTreeMap<String, Segment>,
+    pub public_memory: Vec<PublicMemEntry>,
+    pub dynamic_params: Option<()>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Segment {
+    pub begin_addr: u64,
+    pub stop_ptr: u64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PublicMemEntry {
+    pub address: u64,
+    pub value: FeltValue,
+    pub page: u64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PrivateInput {
+    pub trace_path: String,
+    pub memory_path: String,
+    /// Always empty: runnair has no pedersen builtin.
+    pub pedersen: Vec<()>,
+    /// Always empty: runnair has no range check builtin.
+    pub range_check: Vec<()>,
+}
+
+/// A memory value, serialized as a hex string of its four M31 limbs, most significant limb first.
+#[derive(Clone, Copy, Debug)]
+pub struct FeltValue(pub QM31);
+
+impl Serialize for FeltValue {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let [a, b, c, d] = self.0.to_m31_array().map(|limb| limb.0);
+        serializer.serialize_str(&format!("0x{d:08x}{c:08x}{b:08x}{a:08x}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use stwo_prover::core::fields::m31::M31;
+
+    use super::*;
+
+    #[test]
+    fn test_felt_value_serde() {
+        let felt_value = FeltValue(QM31::from_m31_array([1, 2, 3, 0x7ffffffe].map(M31)));
+        let json = serde_json::to_string(&felt_value).unwrap();
+        assert_eq!(json, r#""0x7ffffffe000000030000000200000001""#);
+    }
+}
@@ -0,0 +1,233 @@
+pub mod json;
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use bytemuck::{bytes_of, Pod, Zeroable};
+use json::{FeltValue, PrivateInput, PublicInput, PublicMemEntry, Segment};
+use stwo_prover::core::fields::m31::M31;
+use stwo_prover::core::fields::qm31::QM31;
+use thiserror::Error;
+
+use crate::memory::relocatable::MaybeRelocatable;
+use crate::memory::segments::SegmentKind;
+use crate::memory::Memory;
+use crate::utils::u32_from_usize;
+use crate::vm::trace::TraceEntry;
+use crate::vm::VM;
+
+pub const PUBLIC_INPUT_FILE: &str = "pub.json";
+pub const PRIVATE_INPUT_FILE: &str = "priv.json";
+pub const MEMORY_FILE: &str = "mem";
+pub const TRACE_FILE: &str = "trace";
+
+const LAYOUT: &str = "plain";
+const PUBLIC_SEGMENTS: [&str; 2] = ["program", "output"];
+
+#[derive(Debug, Error)]
+pub enum ExportError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("The VM must be relocated before exporting.")]
+    NotRelocated,
+    #[error("Trace recording must be enabled to export a run.")]
+    NoTrace,
+    #[error("Memory cell {0} holds a relocatable value.")]
+    RelocatableValue(M31),
+}
+
+/// A single entry from the memory file.
+/// Note: This struct must be kept in sync with the prover's `vm_import::MemEntry`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Pod, Zeroable)]
+pub struct MemEntry {
+    pub addr: u32,
+    pub val: [u32; 4],
+}
+
+/// Writes the public input, private input, memory and trace files of a finished and relocated run
+/// to `dir`, in the format read by the prover's `import_from_vm_output`.
+pub fn export_run(vm: &VM, dir: &Path) -> Result<(), ExportError> {
+    let trace = vm.relocated_trace().ok_or(ExportError::NoTrace)?;
+    let public_input = public_input(vm)?;
+    let private_input = PrivateInput {
+        trace_path: TRACE_FILE.to_string(),
+        memory_path: MEMORY_FILE.to_string(),
+        pedersen: vec![],
+        range_check: vec![],
+    };
+
+    serde_json::to_writer(File::create(dir.join(PUBLIC_INPUT_FILE))?, &public_input)?;
+    serde_json::to_writer(File::create(dir.join(PRIVATE_INPUT_FILE))?, &private_input)?;
+    write_memory(vm.memory(), File::create(dir.join(MEMORY_FILE))?)?;
+    write_trace(trace, File::create(dir.join(TRACE_FILE))?)?;
+
+    Ok(())
+}
+
+/// Writes the relocated memory as consecutive `MemEntry` records, sorted by address.
+pub fn write_memory(memory: &Memory, writer: impl Write) -> Result<(), ExportError> {
+    let mut writer = BufWriter::new(writer);
+    for (addr, value) in sorted_absolute_data(memory)? {
+        let entry = MemEntry {
+            addr: addr.0,
+            val: value.map(|x| x.0),
+        };
+        writer.write_all(bytes_of(&entry))?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Writes the relocated trace as consecutive `TraceEntry` records.
+pub fn write_trace(trace: &[TraceEntry], writer: impl Write) -> Result<(), ExportError> {
+    let mut writer = BufWriter::new(writer);
+    for entry in trace {
+        writer.write_all(bytes_of(entry))?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Returns the public input of a finished and relocated run.
+pub fn public_input(vm: &VM) -> Result<PublicInput, ExportError> {
+    let n_steps = vm.relocated_trace().ok_or(ExportError::NoTrace)?.len();
+    let table = vm.relocation_table().ok_or(ExportError::NotRelocated)?;
+    let memory = vm.memory();
+    let data = sorted_absolute_data(memory)?;
+    let end_addr = data.last().map_or(0, |(addr, _)| u64::from(addr.0) + 1);
+
+    let mut memory_segments: BTreeMap<_, _> = memory
+        .segment_kinds()
+        .iter()
+        .enumerate()
+        .filter_map(|(segment, &kind)| {
+            let begin_addr = u64::from(table[&segment].0);
+            let stop_ptr = begin_addr + memory.segment_size(segment) as u64;
+            let segment = Segment {
+                begin_addr,
+                stop_ptr,
+            };
+            Some((segment_name(kind)?.to_string(), segment))
+        })
+        .collect();
+    // The range check builtin is not supported; the prover expects its segment to exist.
+    memory_segments.insert(
+        "range_check".to_string(),
+        Segment {
+            begin_addr: end_addr,
+            stop_ptr: end_addr,
+        },
+    );
+
+    let segment_data = |name: &str| {
+        let Segment {
+            begin_addr,
+            stop_ptr,
+        } = memory_segments[name];
+        data.iter()
+            .filter(move |(addr, _)| (begin_addr..stop_ptr).contains(&u64::from(addr.0)))
+    };
+    // In proof mode, the stack of the prologue and the returned builtin pointers are public too.
+    let execution_data = vm.public_execution_cells().into_iter().filter_map(|cell| {
+        let addr = cell.relocate(table);
+        let index = data
+            .binary_search_by_key(&addr.0, |(addr, _)| addr.0)
+            .ok()?;
+        Some(&data[index])
+    });
+    let [program, output] = PUBLIC_SEGMENTS;
+    let public_memory = segment_data(program)
+        .chain(execution_data)
+        .chain(segment_data(output))
+        .map(|(addr, value)| PublicMemEntry {
+            address: u64::from(addr.0),
+            value: FeltValue(QM31::from_m31_array(*value)),
+            page: 0,
+        })
+        .collect();
+
+    Ok(PublicInput {
+        layout: LAYOUT.to_string(),
+        rc_min: 0,
+        rc_max: 0,
+        n_steps: n_steps as u64,
+        memory_segments,
+        public_memory,
+        dynamic_params: None,
+    })
+}
+
+/// The name of the segments of `kind` in `memory_segments`, if they are exported.
+fn segment_name(kind: SegmentKind) -> Option<&'static str> {
+    match kind {
+        SegmentKind::Program => Some("program"),
+        SegmentKind::Execution => Some("execution"),
+        SegmentKind::Output => Some("output"),
+        SegmentKind::Input | SegmentKind::ReturnTarget => None,
+    }
+}
+
+/// Returns the relocated memory as `(address, value)` pairs, sorted by address.
+pub fn sorted_absolute_data(memory: &Memory) -> Result<Vec<(M31, [M31; 4])>, ExportError> {
+    memory
+        .absolute_data()
+        .iter()
+        .map(|(addr, value)| {
+            let addr = M31(u32_from_usize(addr));
+            match value {
+                MaybeRelocatable::Absolute(value) => Ok((addr, value.to_m31_array())),
+                MaybeRelocatable::Relocatable(_) => Err(ExportError::RelocatableValue(addr)),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::test_utils::{fibonacci_program, fibonacci_vm};
+    use crate::utils::create_temp_dir;
+
+    #[test]
+    fn test_export_run() {
+        let program_len = fibonacci_program().instructions.len() as u64;
+        let mut vm = fibonacci_vm(5);
+        vm.enable_trace_recording();
+        vm.execute().unwrap();
+        vm.relocate();
+        let output_base = u64::from(vm.relocation_table().unwrap()[&2].0);
+        let dir = create_temp_dir("test_export_run");
+
+        export_run(&vm, &dir).unwrap();
+
+        let public_input: serde_json::Value =
+            serde_json::from_slice(&fs::read(dir.join(PUBLIC_INPUT_FILE)).unwrap()).unwrap();
+        let trace = vm.relocated_trace().unwrap();
+        assert_eq!(public_input["n_steps"], trace.len());
+        assert_eq!(
+            public_input["memory_segments"]["program"],
+            serde_json::json!({ "begin_addr": 1, "stop_ptr": 1 + program_len })
+        );
+        assert_eq!(
+            public_input["memory_segments"]["output"],
+            serde_json::json!({ "begin_addr": output_base, "stop_ptr": output_base + 2 })
+        );
+        let trace_size = fs::metadata(dir.join(TRACE_FILE)).unwrap().len();
+        assert_eq!(trace_size as usize, std::mem::size_of_val(trace));
+        let memory_size = fs::metadata(dir.join(MEMORY_FILE)).unwrap().len();
+        assert_eq!(
+            memory_size as usize,
+            vm.memory().absolute_data().n_written() * std::mem::size_of::<MemEntry>()
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
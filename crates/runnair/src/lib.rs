@@ -0,0 +1,24 @@
+//! A runner for Cairo M31 programs.
+//!
+//! A run loads a [`Program`] with [`Program::from_compiled_file`], registers the processors of
+//! its hints in a [`HintRunner`] and creates a [`VM`] from both, e.g. with
+//! [`VM::create_for_main_entry_point`]. After [`VM::execute`], the [`Memory`], the registers and
+//! the output can be inspected, and [`VM::relocate`] moves every cell to an absolute address for
+//! export to the prover.
+//!
+//! The most used types are re-exported here; the modules hold the rest of the API.
+
+pub mod assembler;
+pub mod disassembler;
+pub mod export;
+pub mod memory;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
+mod utils;
+pub mod vm;
+
+pub use memory::relocatable::{MaybeRelocatable, Relocatable, RelocationTable};
+pub use memory::{MaybeRelocatableAddr, MaybeRelocatableValue, Memory};
+pub use vm::error::{ProgramError, VmError, VmException};
+pub use vm::hints::{HintError, HintProcessor, HintRunner};
+pub use vm::{Input, Program, State, VM};
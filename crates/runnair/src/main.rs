@@ -1,9 +1,220 @@
-use vm::run_fibonacci;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
 
-pub mod memory;
-pub mod utils;
-pub mod vm;
+use clap::{Args, Parser, Subcommand};
+use runner::assembler::{assemble, AssemblerError};
+use runner::disassembler::{disassemble, DisassemblerError};
+use runner::export::{write_memory, write_trace, ExportError};
+use runner::vm::builtins::OutputError;
+use runner::{HintRunner, Input, Program, ProgramError, VmException, VM};
+use thiserror::Error;
+
+#[derive(Debug, Parser)]
+#[command(about = "Runs Cairo M31 programs.")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Runs a compiled program.
+    Run(RunArgs),
+    /// Prints the instructions of a compiled program as mnemonics.
+    Disassemble(DisassembleArgs),
+    /// Assembles a program into compiled program JSON.
+    Assemble(AssembleArgs),
+}
+
+#[derive(Debug, Args)]
+struct RunArgs {
+    /// The compiled program JSON.
+    program: PathBuf,
+    /// The program input JSON, read by hints.
+    #[arg(long)]
+    input: Option<PathBuf>,
+    /// The full name of the function to start from.
+    #[arg(long, default_value = "__main__.main")]
+    entry_point: String,
+    /// Run in proof mode: call the entry point from a prologue that ends in a `jmp rel 0` loop,
+    /// and pad the run to a power of two steps.
+    #[arg(long)]
+    proof_mode: bool,
+    /// Fail if the program does not end within this number of steps.
+    #[arg(long)]
+    max_steps: Option<usize>,
+    /// Fail if the program gets stuck in a loop that makes no progress.
+    #[arg(long)]
+    detect_loops: bool,
+    /// Print the output segment.
+    #[arg(long)]
+    print_output: bool,
+    /// Print the final registers.
+    #[arg(long)]
+    print_registers: bool,
+    /// Print execution statistics.
+    #[arg(long)]
+    print_stats: bool,
+    /// Print the steps executed in each function.
+    #[arg(long)]
+    print_profile: bool,
+    /// Write the steps executed with each call stack to this file, in the folded format read by
+    /// flamegraph tools.
+    #[arg(long)]
+    profile_file: Option<PathBuf>,
+    /// Write the execution resources JSON to this file.
+    #[arg(long)]
+    resources_file: Option<PathBuf>,
+    /// Write the relocated trace to this file.
+    #[arg(long)]
+    trace_file: Option<PathBuf>,
+    /// Write the relocated memory to this file.
+    #[arg(long)]
+    memory_file: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+struct DisassembleArgs {
+    /// The compiled program JSON.
+    program: PathBuf,
+}
+
+#[derive(Debug, Args)]
+struct AssembleArgs {
+    /// The program assembly.
+    source: PathBuf,
+    /// Write the compiled program JSON to this file instead of stdout.
+    #[arg(long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Debug, Error)]
+enum CliError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid input: {0}")]
+    Input(#[from] serde_json::Error),
+    #[error(transparent)]
+    Program(#[from] ProgramError),
+    #[error(transparent)]
+    Vm(#[from] VmException),
+    #[error(transparent)]
+    Output(#[from] OutputError),
+    #[error(transparent)]
+    Export(#[from] ExportError),
+    #[error(transparent)]
+    Disassembler(#[from] DisassemblerError),
+    #[error(transparent)]
+    Assembler(#[from] AssemblerError),
+}
 
 fn main() {
-    run_fibonacci();
+    let cli = Cli::parse();
+    let result = match cli.command {
+        Command::Run(args) => run(args),
+        Command::Disassemble(args) => disassemble_program(args),
+        Command::Assemble(args) => assemble_program(args),
+    };
+
+    if let Err(error) = result {
+        eprintln!("{error}");
+        std::process::exit(1);
+    }
+}
+
+fn run(args: RunArgs) -> Result<(), CliError> {
+    let program = Program::from_compiled_file(args.program)?;
+    let input: Input = match args.input {
+        Some(path) => serde_json::from_reader(BufReader::new(File::open(path)?))?,
+        None => Input::default(),
+    };
+
+    let hint_runner = HintRunner::new(input);
+    let mut vm = if args.proof_mode {
+        VM::create_for_proof_mode(program, &args.entry_point, hint_runner)?
+    } else {
+        VM::create_for_entry_point(program, &args.entry_point, &[], hint_runner)?
+    };
+    if let Some(max_steps) = args.max_steps {
+        vm.set_max_steps(max_steps);
+    }
+    if args.detect_loops {
+        vm.enable_loop_detection();
+    }
+    if args.trace_file.is_some() {
+        vm.enable_trace_recording();
+    }
+    if args.print_profile || args.profile_file.is_some() {
+        vm.enable_profiling();
+    }
+    vm.execute()?;
+
+    if args.print_output {
+        println!("Output:");
+        for value in vm.output()? {
+            println!("  {value}");
+        }
+    }
+    if args.print_registers {
+        let state = vm.state();
+        println!("Registers:");
+        println!("  ap: {}", state.ap());
+        println!("  fp: {}", state.fp());
+        println!("  pc: {}", state.pc());
+    }
+    if let Some(profile) = vm.profile() {
+        if args.print_profile {
+            println!("Profile:");
+            println!("  {:>10} {:>10}  function", "inclusive", "exclusive");
+            for function in &profile.functions {
+                println!(
+                    "  {:>10} {:>10}  {}",
+                    function.inclusive_steps, function.exclusive_steps, function.name
+                );
+            }
+        }
+        if let Some(path) = args.profile_file {
+            profile.write_folded(File::create(path)?)?;
+        }
+    }
+    if let Some(path) = args.resources_file {
+        serde_json::to_writer_pretty(File::create(path)?, &vm.execution_resources())?;
+    }
+
+    vm.relocate();
+
+    if args.print_stats {
+        println!("Statistics:");
+        println!("  steps: {}", vm.n_steps());
+        println!(
+            "  memory cells: {}",
+            vm.memory().absolute_data().n_written()
+        );
+    }
+    if let (Some(path), Some(trace)) = (args.trace_file, vm.relocated_trace()) {
+        write_trace(trace, File::create(path)?)?;
+    }
+    if let Some(path) = args.memory_file {
+        write_memory(vm.memory(), File::create(path)?)?;
+    }
+
+    Ok(())
+}
+
+fn disassemble_program(args: DisassembleArgs) -> Result<(), CliError> {
+    let program = Program::from_compiled_file(args.program)?;
+    print!("{}", disassemble(&program)?);
+
+    Ok(())
+}
+
+fn assemble_program(args: AssembleArgs) -> Result<(), CliError> {
+    let program = assemble(&std::fs::read_to_string(args.source)?)?;
+    match args.output {
+        Some(path) => serde_json::to_writer_pretty(File::create(path)?, &program)?,
+        None => println!("{}", serde_json::to_string_pretty(&program)?),
+    }
+
+    Ok(())
 }
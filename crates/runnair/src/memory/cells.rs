@@ -0,0 +1,298 @@
+use std::collections::BTreeMap;
+
+use num_traits::Zero;
+use serde::{Deserialize, Serialize};
+use stwo_prover::core::fields::m31::M31;
+use stwo_prover::core::fields::qm31::QM31;
+
+use super::relocatable::{MaybeRelocatable, Relocatable};
+use super::MaybeRelocatableValue;
+use crate::utils::{u32_from_usize, usize_from_u32};
+
+/// A write grows the dense cells if it lands less than this many cells, or less than their
+/// number, past them; farther writes are kept sparse, so that a few high offsets (e.g. absolute
+/// addresses) don't allocate all the cells below them.
+const MAX_DENSE_GAP: usize = 1 << 16;
+
+/// Dense storage for a contiguous range of memory cells, e.g. a segment, indexed by offset.
+///
+/// Values are kept as plain `QM31`s next to two bitmaps: one marks the written cells, and the
+/// other tags the relocatable values, which are packed as `(segment, offset, 0, 0)`. This costs 16
+/// bytes and 2 bits per cell, instead of a full `Option<MaybeRelocatableValue>`. The cells too far
+/// past the dense ones are kept in a sorted map instead.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "CellsRaw")]
+pub struct Cells {
+    values: Vec<QM31>,
+    written: Bitmap,
+    relocatable: Bitmap,
+    /// The written cells past the dense ones, by offset.
+    sparse: BTreeMap<usize, MaybeRelocatableValue>,
+}
+
+/// The serialized form of `Cells`, whose bitmaps are checked against the values on
+/// deserialization.
+#[derive(Deserialize)]
+struct CellsRaw {
+    values: Vec<QM31>,
+    written: Bitmap,
+    relocatable: Bitmap,
+    sparse: BTreeMap<usize, MaybeRelocatableValue>,
+}
+
+impl TryFrom<CellsRaw> for Cells {
+    type Error = String;
+
+    fn try_from(raw_cells: CellsRaw) -> Result<Self, Self::Error> {
+        let CellsRaw {
+            values,
+            written,
+            relocatable,
+            sparse,
+        } = raw_cells;
+        if !written.has_len(values.len()) || !relocatable.has_len(values.len()) {
+            return Err(format!(
+                "Cell bitmaps do not match the {} cell values.",
+                values.len()
+            ));
+        }
+        if sparse.range(..values.len()).next().is_some() {
+            return Err(format!(
+                "Sparse cells overlap the {} dense cells.",
+                values.len()
+            ));
+        }
+
+        Ok(Self {
+            values,
+            written,
+            relocatable,
+            sparse,
+        })
+    }
+}
+
+impl Cells {
+    /// The number of cells up to and including the last written cell.
+    pub fn len(&self) -> usize {
+        self.sparse
+            .last_key_value()
+            .map_or(self.values.len(), |(&offset, _)| offset + 1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of written cells.
+    pub fn n_written(&self) -> usize {
+        self.written.count_ones() + self.sparse.len()
+    }
+
+    pub fn get(&self, offset: usize) -> Option<MaybeRelocatableValue> {
+        if offset >= self.values.len() {
+            return self.sparse.get(&offset).copied();
+        }
+        if !self.written.get(offset) {
+            return None;
+        }
+        let value = self.values[offset];
+        Some(if self.relocatable.get(offset) {
+            unpack_relocatable(value).into()
+        } else {
+            value.into()
+        })
+    }
+
+    /// Writes `value` to the cell at `offset`, growing the storage as needed, and returns the
+    /// previous value.
+    pub(super) fn set(
+        &mut self,
+        offset: usize,
+        value: MaybeRelocatableValue,
+    ) -> Option<MaybeRelocatableValue> {
+        let n_dense = self.values.len();
+        if offset >= n_dense + n_dense.max(MAX_DENSE_GAP) {
+            return self.sparse.insert(offset, value);
+        }
+
+        let previous = self.get(offset);
+        if offset >= n_dense {
+            self.values.resize(offset + 1, QM31::zero());
+            self.written.resize(offset + 1);
+            self.relocatable.resize(offset + 1);
+            // The sparse cells now covered by the dense ones move to them.
+            if self
+                .sparse
+                .first_key_value()
+                .is_some_and(|(&first, _)| first <= offset)
+            {
+                let far = self.sparse.split_off(&(offset + 1));
+                for (offset, value) in std::mem::replace(&mut self.sparse, far) {
+                    self.set_dense(offset, value);
+                }
+            }
+        }
+        self.set_dense(offset, value);
+
+        previous
+    }
+
+    /// Writes `value` to the cell at `offset`, which must be within the dense cells.
+    fn set_dense(&mut self, offset: usize, value: MaybeRelocatableValue) {
+        let (value, is_relocatable) = match value {
+            MaybeRelocatable::Absolute(value) => (value, false),
+            MaybeRelocatable::Relocatable(value) => (pack_relocatable(value), true),
+        };
+        self.values[offset] = value;
+        self.written.set(offset, true);
+        self.relocatable.set(offset, is_relocatable);
+    }
+
+    /// The written cells as `(offset, value)` pairs, in order.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, MaybeRelocatableValue)> + '_ {
+        let dense = (0..self.values.len()).filter_map(|offset| Some((offset, self.get(offset)?)));
+        dense.chain(self.sparse.iter().map(|(&offset, &value)| (offset, value)))
+    }
+}
+
+fn pack_relocatable(Relocatable { segment, offset }: Relocatable) -> QM31 {
+    QM31::from_m31_array([M31(u32_from_usize(segment)), offset, M31(0), M31(0)])
+}
+
+fn unpack_relocatable(value: QM31) -> Relocatable {
+    let [segment, offset, ..] = value.to_m31_array();
+    Relocatable::from((usize_from_u32(segment.0), offset.0))
+}
+
+/// A growable bit vector.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+struct Bitmap {
+    words: Vec<u64>,
+}
+
+impl Bitmap {
+    fn get(&self, index: usize) -> bool {
+        self.words
+            .get(index / 64)
+            .is_some_and(|word| word >> (index % 64) & 1 == 1)
+    }
+
+    fn set(&mut self, index: usize, bit: bool) {
+        let word = &mut self.words[index / 64];
+        let mask = 1 << (index % 64);
+        if bit {
+            *word |= mask;
+        } else {
+            *word &= !mask;
+        }
+    }
+
+    /// Whether the bitmap holds exactly `len` bits, as left by `resize`, with no bit set past them.
+    fn has_len(&self, len: usize) -> bool {
+        if self.words.len() != len.div_ceil(64) {
+            return false;
+        }
+        match (self.words.last(), len % 64) {
+            (Some(word), n_used_bits) if n_used_bits != 0 => word >> n_used_bits == 0,
+            _ => true,
+        }
+    }
+
+    /// Makes room for `len` bits; new bits are unset.
+    fn resize(&mut self, len: usize) {
+        self.words.resize(len.div_ceil(64), 0);
+    }
+
+    fn count_ones(&self) -> usize {
+        self.words
+            .iter()
+            .map(|word| usize_from_u32(word.count_ones()))
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cells() {
+        let mut cells = Cells::default();
+        let relocatable = Relocatable::from((3, 7));
+        assert_eq!(cells.set(1, M31(5).into()), None);
+        assert_eq!(cells.set(70, relocatable.into()), None);
+
+        assert_eq!(cells.len(), 71);
+        assert_eq!(cells.n_written(), 2);
+        assert_eq!(cells.get(0), None);
+        assert_eq!(cells.get(1), Some(M31(5).into()));
+        assert_eq!(cells.get(70), Some(relocatable.into()));
+        assert_eq!(cells.get(100), None);
+
+        assert_eq!(cells.set(70, M31(2).into()), Some(relocatable.into()));
+        assert_eq!(
+            cells.iter().collect::<Vec<_>>(),
+            [(1, M31(5).into()), (70, M31(2).into())]
+        );
+    }
+
+    #[test]
+    fn test_sparse_cells() {
+        let mut cells = Cells::default();
+        let far = 1 << 30;
+        assert_eq!(cells.set(far, M31(1).into()), None);
+        assert_eq!(cells.set(MAX_DENSE_GAP + 10, M31(2).into()), None);
+
+        assert!(cells.values.is_empty());
+        assert_eq!(cells.len(), far + 1);
+        assert_eq!(cells.n_written(), 2);
+        assert_eq!(cells.get(far), Some(M31(1).into()));
+
+        // Grow the dense cells past the sparse cell at `MAX_DENSE_GAP + 10`, which moves to them.
+        cells.set(MAX_DENSE_GAP - 1, M31(3).into());
+        assert_eq!(
+            cells.set(MAX_DENSE_GAP + 10, M31(4).into()),
+            Some(M31(2).into())
+        );
+        assert_eq!(cells.set(MAX_DENSE_GAP + 20, M31(5).into()), None);
+
+        assert_eq!(cells.values.len(), MAX_DENSE_GAP + 21);
+        assert_eq!(cells.sparse.len(), 1);
+        assert_eq!(
+            cells.iter().collect::<Vec<_>>(),
+            [
+                (MAX_DENSE_GAP - 1, M31(3).into()),
+                (MAX_DENSE_GAP + 10, M31(4).into()),
+                (MAX_DENSE_GAP + 20, M31(5).into()),
+                (far, M31(1).into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_deserialize_cells() {
+        let mut cells = Cells::default();
+        cells.set(70, M31(5).into());
+        let json = serde_json::to_value(&cells).unwrap();
+        assert_eq!(
+            serde_json::from_value::<Cells>(json.clone()).unwrap(),
+            cells
+        );
+
+        let mut missing_word = json.clone();
+        missing_word["written"]["words"]
+            .as_array_mut()
+            .unwrap()
+            .pop();
+        let mut bit_past_end = json.clone();
+        bit_past_end["relocatable"]["words"][1] = (1u64 << 7).into();
+        let mut sparse_dense_cell = json;
+        sparse_dense_cell["sparse"]["3"] =
+            serde_json::to_value(MaybeRelocatableValue::from(M31(5))).unwrap();
+
+        assert!(serde_json::from_value::<Cells>(missing_word).is_err());
+        assert!(serde_json::from_value::<Cells>(bit_past_end).is_err());
+        assert!(serde_json::from_value::<Cells>(sparse_dense_cell).is_err());
+    }
+}
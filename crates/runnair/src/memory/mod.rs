@@ -1,14 +1,17 @@
-use std::collections::HashMap;
-use std::ops::Index;
-
+use cells::Cells;
 use relocatable::{Relocatable, RelocationTable, Segment};
+use segments::SegmentKind;
+use serde::{Deserialize, Serialize};
 use stwo_prover::core::fields::m31::M31;
 use stwo_prover::core::fields::qm31::QM31;
+use thiserror::Error;
 
 use self::relocatable::MaybeRelocatable;
-use crate::utils::{maybe_resize, u32_from_usize, usize_from_u32};
+use crate::utils::{maybe_resize, usize_from_u32};
 
+pub mod cells;
 pub mod relocatable;
+pub mod segments;
 
 pub type MaybeRelocatableAddr = MaybeRelocatable<M31>;
 pub type MaybeRelocatableValue = MaybeRelocatable<QM31>;
@@ -16,124 +19,257 @@ pub type MaybeRelocatableValue = MaybeRelocatable<QM31>;
 // TODO: confirm this limit.
 const MAX_MEMORY_SIZE_BITS: u8 = 30;
 
-#[derive(Clone, Debug, Default)]
-pub struct Memory {
-    // TODO(alont) Consdier changing the implementation to segment -> (offset -> value) for memory
-    // locality.
-    relocatable_data: Vec<Vec<Option<MaybeRelocatable<QM31>>>>,
-    // TODO: convert to a vector.
-    absolute_data: HashMap<M31, MaybeRelocatableValue>,
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Error)]
+pub enum MemoryError {
+    #[error("Inconsistent memory write to {address:?}: {existing:?} != {value:?}.")]
+    InconsistentWrite {
+        address: MaybeRelocatableAddr,
+        existing: MaybeRelocatableValue,
+        value: MaybeRelocatableValue,
+    },
+    #[error("Unknown segment: {0}.")]
+    UnknownSegment(Segment),
+    #[error("Max memory size is 2 ** {MAX_MEMORY_SIZE_BITS}; got address: {0}.")]
+    AddressOutOfRange(M31),
 }
 
-impl<T: Into<MaybeRelocatableAddr>> Index<T> for Memory {
-    type Output = MaybeRelocatableValue;
-    fn index(&self, index: T) -> &Self::Output {
-        match index.into() {
-            MaybeRelocatableAddr::Absolute(addr) => &self.absolute_data[&addr],
-            MaybeRelocatable::Relocatable(Relocatable { segment, offset }) => {
-                let segment_info = &self.relocatable_data[segment];
-                let offset = usize_from_u32(offset.0);
-
-                segment_info[offset].as_ref().unwrap_or_else(|| {
-                    panic!("Offset {offset} is out of bounds for segment {segment}.");
-                })
-            }
-        }
-    }
+/// A write that changed the value of a cell, through `Memory::overwrite`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Overwrite {
+    pub address: MaybeRelocatableAddr,
+    pub previous: MaybeRelocatableValue,
+    pub value: MaybeRelocatableValue,
 }
 
-impl<T: Into<MaybeRelocatableAddr>, S: Into<MaybeRelocatableValue>> Extend<(T, S)> for Memory {
-    fn extend<I: IntoIterator<Item = (T, S)>>(&mut self, iter: I) {
-        for (key, value) in iter {
-            self.insert(key, value);
-        }
-    }
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(try_from = "MemoryRaw")]
+pub struct Memory {
+    /// The cells of each segment, by segment index.
+    relocatable_data: Vec<Cells>,
+    /// The cells at absolute addresses, by address.
+    absolute_data: Cells,
+    n_writes: usize,
+    overwrites: Vec<Overwrite>,
+    segment_kinds: Vec<SegmentKind>,
+    /// The finalized size of each segment, if finalized.
+    segment_sizes: Vec<Option<usize>>,
 }
 
-impl<T: Into<MaybeRelocatableAddr>, S: Into<MaybeRelocatableValue>> FromIterator<(T, S)>
-    for Memory
-{
-    fn from_iter<I: IntoIterator<Item = (T, S)>>(iter: I) -> Self {
-        let mut memory = Self::default();
+/// The serialized form of `Memory`, checked on deserialization to only use added segments, as
+/// `insert` ensures.
+#[derive(Deserialize)]
+struct MemoryRaw {
+    relocatable_data: Vec<Cells>,
+    absolute_data: Cells,
+    n_writes: usize,
+    overwrites: Vec<Overwrite>,
+    segment_kinds: Vec<SegmentKind>,
+    segment_sizes: Vec<Option<usize>>,
+}
 
-        for (key, value) in iter {
-            memory.insert(key, value);
+impl TryFrom<MemoryRaw> for Memory {
+    type Error = String;
+
+    fn try_from(raw_memory: MemoryRaw) -> Result<Self, Self::Error> {
+        let memory = Self {
+            relocatable_data: raw_memory.relocatable_data,
+            absolute_data: raw_memory.absolute_data,
+            n_writes: raw_memory.n_writes,
+            overwrites: raw_memory.overwrites,
+            segment_kinds: raw_memory.segment_kinds,
+            segment_sizes: raw_memory.segment_sizes,
+        };
+        let n_segments = memory.segment_kinds.len();
+        if memory.segment_sizes.len() != n_segments {
+            return Err(format!(
+                "Expected {n_segments} segment sizes; got {}.",
+                memory.segment_sizes.len()
+            ));
+        }
+        if memory.relocatable_data.len() > n_segments {
+            return Err(format!(
+                "Expected at most {n_segments} segments of cells; got {}.",
+                memory.relocatable_data.len()
+            ));
+        }
+        let values = memory
+            .relocatable_data
+            .iter()
+            .chain([&memory.absolute_data])
+            .flat_map(|cells| cells.iter().map(|(_, value)| value));
+        for value in values {
+            if let MaybeRelocatable::Relocatable(value) = value {
+                if value.segment >= n_segments {
+                    return Err(format!("Unknown segment in value {value}."));
+                }
+            }
         }
 
-        memory
+        Ok(memory)
     }
 }
 
 impl Memory {
-    pub fn relocate(&mut self, table: &RelocationTable) {
-        let relocated_data = self
-            .relocatable_data
-            .iter()
-            .enumerate()
-            .flat_map(|(segment, segment_info)| relocate_segment(segment, segment_info, table));
+    /// The cells at absolute addresses, which include all the cells once relocated.
+    pub fn absolute_data(&self) -> &Cells {
+        &self.absolute_data
+    }
+
+    /// The number of writes so far that changed memory, i.e. that wrote a new cell or a different
+    /// value. Rewrites of the same value are not counted.
+    pub fn n_writes(&self) -> usize {
+        self.n_writes
+    }
 
-        self.absolute_data.extend(relocated_data);
-        self.relocatable_data.clear();
+    /// The writes that changed the value of a cell, in order.
+    pub fn overwrites(&self) -> &[Overwrite] {
+        &self.overwrites
     }
 
+    /// Moves all the cells to absolute addresses, finalizing the size of every segment. `table`
+    /// must have an entry for every added segment, as the one from `relocation_table` does.
+    pub fn relocate(&mut self, table: &RelocationTable) {
+        self.finalize_segments();
+        let relocatable_data = std::mem::take(&mut self.relocatable_data);
+        for (segment, cells) in relocatable_data.iter().enumerate() {
+            if cells.is_empty() {
+                continue;
+            }
+            let base = usize_from_u32(table[&segment].0);
+            for (offset, value) in cells.iter() {
+                let value = value.relocate(table).into();
+                self.absolute_data.set(base + offset, value);
+            }
+        }
+    }
+
+    /// Writes `value` to the cell at `key`. Memory is write-once: fails if the cell already holds
+    /// a different value, and accepts rewriting the same value. Also fails if the address or the
+    /// value is in a segment that was not added, or if the address is out of range.
     pub fn insert<T: Into<MaybeRelocatableAddr>, S: Into<MaybeRelocatableValue>>(
         &mut self,
         key: T,
         value: S,
-    ) -> Option<MaybeRelocatableValue> {
+    ) -> Result<(), MemoryError> {
+        let address = key.into();
         let value = value.into();
+        self.validate(address, value)?;
+        match self.get(address) {
+            Some(existing) if existing != value => Err(MemoryError::InconsistentWrite {
+                address,
+                existing,
+                value,
+            }),
+            _ => {
+                self.write(address, value);
+                Ok(())
+            }
+        }
+    }
 
-        match key.into() {
-            MaybeRelocatableAddr::Absolute(addr) => {
-                validate_address(addr);
-                self.absolute_data.insert(addr, value)
+    /// Writes each value to its cell, as `insert`, and stops at the first failing write.
+    pub fn insert_all<T: Into<MaybeRelocatableAddr>, S: Into<MaybeRelocatableValue>>(
+        &mut self,
+        cells: impl IntoIterator<Item = (T, S)>,
+    ) -> Result<(), MemoryError> {
+        cells
+            .into_iter()
+            .try_for_each(|(key, value)| self.insert(key, value))
+    }
+
+    /// Writes `value` to the cell at `key`, even if it holds a different value, and returns the
+    /// previous value.
+    ///
+    /// This breaks the write-once semantics that the prover relies on, so it is only meant for
+    /// hints that must rewrite memory. Every change of a value is logged and recorded in
+    /// `overwrites`. Fails like `insert` on a segment that was not added or an address out of
+    /// range.
+    pub fn overwrite<T: Into<MaybeRelocatableAddr>, S: Into<MaybeRelocatableValue>>(
+        &mut self,
+        key: T,
+        value: S,
+    ) -> Result<Option<MaybeRelocatableValue>, MemoryError> {
+        let address = key.into();
+        let value = value.into();
+        self.validate(address, value)?;
+        let previous = self.write(address, value);
+        if let Some(previous) = previous.filter(|&previous| previous != value) {
+            tracing::warn!("Overwriting {address:?}: {previous:?} -> {value:?}.");
+            self.overwrites.push(Overwrite {
+                address,
+                previous,
+                value,
+            });
+        }
+
+        Ok(previous)
+    }
+
+    /// Checks that the relocatable address and value, if any, are in added segments, so that the
+    /// memory can always be relocated, and that an absolute address is within the memory size.
+    fn validate(
+        &self,
+        address: MaybeRelocatableAddr,
+        value: MaybeRelocatableValue,
+    ) -> Result<(), MemoryError> {
+        let validate = |segment: Segment| {
+            if segment < self.segment_kinds.len() {
+                Ok(())
+            } else {
+                Err(MemoryError::UnknownSegment(segment))
             }
-            MaybeRelocatableAddr::Relocatable(Relocatable { segment, offset }) => {
-                maybe_resize(&mut self.relocatable_data, segment, Vec::new());
+        };
+        match address {
+            MaybeRelocatable::Relocatable(address) => validate(address.segment)?,
+            MaybeRelocatable::Absolute(address) => validate_address(address)?,
+        }
+        if let MaybeRelocatable::Relocatable(value) = value {
+            validate(value.segment)?;
+        }
 
-                let segment_info = &mut self.relocatable_data[segment];
-                let offset = usize_from_u32(offset.0);
-                maybe_resize(segment_info, offset, None);
+        Ok(())
+    }
 
-                std::mem::replace(&mut segment_info[offset], Some(value))
+    fn write(
+        &mut self,
+        address: MaybeRelocatableAddr,
+        value: MaybeRelocatableValue,
+    ) -> Option<MaybeRelocatableValue> {
+        let previous = match address {
+            MaybeRelocatableAddr::Absolute(addr) => {
+                self.absolute_data.set(usize_from_u32(addr.0), value)
             }
+            MaybeRelocatableAddr::Relocatable(Relocatable { segment, offset }) => {
+                maybe_resize(&mut self.relocatable_data, segment, Cells::default());
+                self.relocatable_data[segment].set(usize_from_u32(offset.0), value)
+            }
+        };
+        if previous != Some(value) {
+            self.n_writes += 1;
         }
+
+        previous
     }
 
     pub fn get<T: Into<MaybeRelocatableAddr>>(&self, key: T) -> Option<MaybeRelocatableValue> {
         match key.into() {
-            MaybeRelocatableAddr::Absolute(addr) => self.absolute_data.get(&addr).copied(),
-            MaybeRelocatableAddr::Relocatable(Relocatable { segment, offset }) => *self
+            MaybeRelocatableAddr::Absolute(addr) => self.absolute_data.get(usize_from_u32(addr.0)),
+            MaybeRelocatableAddr::Relocatable(Relocatable { segment, offset }) => self
                 .relocatable_data
                 .get(segment)?
-                .get(usize_from_u32(offset.0))?,
+                .get(usize_from_u32(offset.0)),
         }
     }
 }
 
 // Utils.
 
-fn validate_address(address: M31) {
+fn validate_address(address: M31) -> Result<(), MemoryError> {
     if address.0 > (1 << MAX_MEMORY_SIZE_BITS) {
-        panic!("Max memory size is 2 ** {MAX_MEMORY_SIZE_BITS}; got address: {address}.")
+        return Err(MemoryError::AddressOutOfRange(address));
     }
-}
 
-fn relocate_segment(
-    segment: Segment,
-    segment_info: &[Option<MaybeRelocatable<QM31>>],
-    table: &RelocationTable,
-) -> Vec<(M31, MaybeRelocatableValue)> {
-    segment_info
-        .iter()
-        .enumerate()
-        .filter_map(move |(offset, option_value)| {
-            option_value.as_ref().map(|value| {
-                let key = Relocatable::from((segment, u32_from_usize(offset)));
-                (key.relocate(table), value.relocate(table).into())
-            })
-        })
-        .collect()
+    Ok(())
 }
 
 #[cfg(test)]
@@ -143,19 +279,131 @@ mod test {
     use stwo_prover::core::fields::qm31::QM31;
 
     use crate::memory::relocatable::Relocatable;
-    use crate::memory::Memory;
+    use crate::memory::segments::SegmentKind;
+    use crate::memory::{Memory, MemoryError, Overwrite};
 
     #[test]
     fn test_relocate_memory() {
         let mut memory = Memory::default();
-        memory.insert(Relocatable::from((0, 0)), QM31::zero());
-        memory.insert(Relocatable::from((1, 1)), Relocatable::from((1, 12)));
+        let program = memory.add_segment(SegmentKind::Program);
+        let execution = memory.add_segment(SegmentKind::Execution);
+        memory.insert(program, QM31::zero()).unwrap();
+        memory
+            .insert(execution + M31(1), execution + M31(12))
+            .unwrap();
+
+        let table = memory.relocation_table();
+        memory.relocate(&table);
+
+        assert_eq!(memory.get(M31(1)), Some(QM31::zero().into()));
+        assert_eq!(memory.get(M31(3)), Some(QM31::from(M31(14)).into()));
+        assert_eq!(memory.segment_size(execution.segment), 2);
+    }
+
+    #[test]
+    fn test_write_once() {
+        let mut memory = Memory::default();
+        let execution = memory.add_segment(SegmentKind::Execution);
+        memory.insert(execution, M31(1)).unwrap();
+
+        // Rewriting the same value is allowed.
+        memory.insert(execution, M31(1)).unwrap();
+
+        assert_eq!(
+            memory.insert(execution, M31(2)),
+            Err(MemoryError::InconsistentWrite {
+                address: execution.into(),
+                existing: M31(1).into(),
+                value: M31(2).into(),
+            })
+        );
+        assert_eq!(memory.get(execution), Some(M31(1).into()));
+        assert!(memory.overwrites().is_empty());
+    }
+
+    #[test]
+    fn test_overwrite() {
+        let mut memory = Memory::default();
+        let execution = memory.add_segment(SegmentKind::Execution);
+        memory.insert(execution, M31(1)).unwrap();
+
+        assert_eq!(memory.overwrite(execution, M31(1)), Ok(Some(M31(1).into())));
+        assert!(memory.overwrites().is_empty());
+        assert_eq!(memory.overwrite(execution, M31(2)), Ok(Some(M31(1).into())));
+
+        assert_eq!(memory.get(execution), Some(M31(2).into()));
+        assert_eq!(
+            memory.overwrites(),
+            [Overwrite {
+                address: execution.into(),
+                previous: M31(1).into(),
+                value: M31(2).into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_address_out_of_range() {
+        let mut memory = Memory::default();
+        let address = M31((1 << 30) + 1);
+
+        assert_eq!(
+            memory.insert(address, M31(1)),
+            Err(MemoryError::AddressOutOfRange(address))
+        );
+        assert_eq!(
+            memory.overwrite(address, M31(1)),
+            Err(MemoryError::AddressOutOfRange(address))
+        );
+    }
+
+    #[test]
+    fn test_unknown_segment() {
+        let mut memory = Memory::default();
+        let execution = memory.add_segment(SegmentKind::Execution);
+        let unknown = Relocatable::from((1, 0));
 
-        let table = [(0, M31(1)), (1, M31(1234))].iter().cloned().collect();
+        assert_eq!(
+            memory.insert(unknown, M31(1)),
+            Err(MemoryError::UnknownSegment(1))
+        );
+        assert_eq!(
+            memory.insert(execution, unknown),
+            Err(MemoryError::UnknownSegment(1))
+        );
+        assert_eq!(
+            memory.overwrite(unknown, M31(1)),
+            Err(MemoryError::UnknownSegment(1))
+        );
+        assert_eq!(
+            memory.finalize_segment(1, 0),
+            Err(MemoryError::UnknownSegment(1))
+        );
+        assert_eq!(memory.n_writes(), 0);
 
+        let table = memory.relocation_table();
         memory.relocate(&table);
+    }
+
+    #[test]
+    fn test_deserialize_unknown_segment() {
+        let mut memory = Memory::default();
+        let program = memory.add_segment(SegmentKind::Program);
+        let execution = memory.add_segment(SegmentKind::Execution);
+        memory.insert(program, execution).unwrap();
+        let json = serde_json::to_value(&memory).unwrap();
+        assert!(serde_json::from_value::<Memory>(json.clone()).is_ok());
+
+        let mut unknown_segment = json;
+        unknown_segment["segment_kinds"]
+            .as_array_mut()
+            .unwrap()
+            .pop();
+        unknown_segment["segment_sizes"]
+            .as_array_mut()
+            .unwrap()
+            .pop();
 
-        assert_eq!(memory[M31(1)], QM31::zero().into());
-        assert_eq!(memory[M31(1235)], QM31::from(M31(1246)).into());
+        assert!(serde_json::from_value::<Memory>(unknown_segment).is_err());
     }
 }
@@ -1,19 +1,22 @@
 use std::collections::HashMap;
+use std::fmt::{self, Display};
 use std::ops::{Add, Div, Mul, Sub};
 
 use num_traits::Zero;
+use serde::{Deserialize, Serialize};
 use stwo_prover::core::fields::m31::M31;
 use stwo_prover::core::fields::qm31::QM31;
+use thiserror::Error;
 
 pub(crate) type Segment = usize;
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub struct Relocatable {
     pub segment: Segment,
     pub offset: M31,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub enum MaybeRelocatable<T: From<M31>> {
     Relocatable(Relocatable),
     Absolute(T),
@@ -21,6 +24,58 @@ pub enum MaybeRelocatable<T: From<M31>> {
 
 pub type RelocationTable = HashMap<Segment, M31>;
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Error)]
+pub enum RelocatableError {
+    #[error("Cannot add two relocatables.")]
+    AddRelocatables,
+    #[error("Cannot subtract a relocatable from an absolute.")]
+    SubRelocatableFromAbsolute,
+    #[error("Cannot subtract relocatables from different segments.")]
+    SubDifferentSegments,
+    #[error("Multiplication involving relocatable values is not possible.")]
+    MulRelocatable,
+    #[error("Division involving relocatable values is not possible.")]
+    DivRelocatable,
+    #[error("Value {0} is not in the base field.")]
+    NonBaseField(QM31),
+    #[error("Expected an absolute value, got {0:?}.")]
+    ExpectedAbsolute(Relocatable),
+}
+
+// Fallible counterparts of the arithmetic operators; the operators themselves panic on error.
+
+pub trait TryAdd<Rhs = Self> {
+    type Output;
+    fn try_add(self, rhs: Rhs) -> Result<Self::Output, RelocatableError>;
+}
+
+pub trait TrySub<Rhs = Self> {
+    type Output;
+    fn try_sub(self, rhs: Rhs) -> Result<Self::Output, RelocatableError>;
+}
+
+pub trait TryMul<Rhs = Self> {
+    type Output;
+    fn try_mul(self, rhs: Rhs) -> Result<Self::Output, RelocatableError>;
+}
+
+pub trait TryDiv<Rhs = Self> {
+    type Output;
+    fn try_div(self, rhs: Rhs) -> Result<Self::Output, RelocatableError>;
+}
+
+/// Implements a panicking operator in terms of its fallible counterpart.
+macro_rules! impl_op_with_try_op {
+    ($op:ident, $method:ident, $try_op:ident, $try_method:ident, $lhs:ty, $rhs:ty) => {
+        impl $op<$rhs> for $lhs {
+            type Output = <$lhs as $try_op<$rhs>>::Output;
+            fn $method(self, rhs: $rhs) -> Self::Output {
+                self.$try_method(rhs).unwrap_or_else(|err| panic!("{err}"))
+            }
+        }
+    };
+}
+
 impl Relocatable {
     pub fn relocate(self, table: &RelocationTable) -> M31 {
         table[&self.segment] + self.offset
@@ -36,6 +91,21 @@ impl<T: From<M31> + Copy> MaybeRelocatable<T> {
     }
 }
 
+impl Display for Relocatable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.segment, self.offset)
+    }
+}
+
+impl<T: From<M31> + Display> Display for MaybeRelocatable<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MaybeRelocatable::Relocatable(x) => x.fmt(f),
+            MaybeRelocatable::Absolute(x) => x.fmt(f),
+        }
+    }
+}
+
 impl From<(Segment, M31)> for Relocatable {
     fn from((segment, offset): (Segment, M31)) -> Self {
         Relocatable { segment, offset }
@@ -88,11 +158,33 @@ impl Add<M31> for Relocatable {
     }
 }
 
+/// Checks that the input is in the base field and returns the projection to the base field.
+fn project_on_felt(x: QM31) -> Result<M31, RelocatableError> {
+    if !x.1.is_zero() || !x.0 .1.is_zero() {
+        return Err(RelocatableError::NonBaseField(x));
+    }
+    Ok(x.0 .0)
+}
+
+/// For an absolute value, checks that the input is in the base field and returns the projection to
+/// the base field.
+/// For a relocatable value, simply returns as-is.
+impl TryFrom<MaybeRelocatable<QM31>> for MaybeRelocatable<M31> {
+    type Error = RelocatableError;
+
+    fn try_from(x: MaybeRelocatable<QM31>) -> Result<Self, Self::Error> {
+        Ok(match x {
+            MaybeRelocatable::Relocatable(x) => MaybeRelocatable::Relocatable(x),
+            MaybeRelocatable::Absolute(x) => MaybeRelocatable::Absolute(project_on_felt(x)?),
+        })
+    }
+}
+
 // TODO(alont): Can this be generalized?
-impl Add<MaybeRelocatable<M31>> for MaybeRelocatable<M31> {
+impl TryAdd for MaybeRelocatable<M31> {
     type Output = Self;
-    fn add(self, rhs: Self) -> Self {
-        match (self, rhs) {
+    fn try_add(self, rhs: Self) -> Result<Self, RelocatableError> {
+        Ok(match (self, rhs) {
             (MaybeRelocatable::Relocatable(lhs), MaybeRelocatable::Absolute(rhs)) => {
                 MaybeRelocatable::Relocatable(lhs + rhs)
             }
@@ -100,93 +192,108 @@ impl Add<MaybeRelocatable<M31>> for MaybeRelocatable<M31> {
                 MaybeRelocatable::Relocatable(rhs + lhs)
             }
             (MaybeRelocatable::Relocatable(_), MaybeRelocatable::Relocatable(_)) => {
-                panic!("Cannot add two relocatables.")
+                return Err(RelocatableError::AddRelocatables)
             }
             (MaybeRelocatable::Absolute(lhs), MaybeRelocatable::Absolute(rhs)) => {
                 MaybeRelocatable::Absolute(lhs + rhs)
             }
-        }
+        })
     }
 }
 
-/// Assert that the input is in the base field and return the projection to the base field.
-fn assert_and_project_on_felt(x: QM31) -> M31 {
-    assert!(x.1.is_zero());
-    assert!(x.0 .1.is_zero());
-    x.0 .0
-}
-
-// TODO: change to `TryFrom` if we add error handling.
-/// For an absolute value, assert that the input is in the base field and return the projection to
-/// the base field.
-/// For a relocatable value, simply returns as-is.
-pub(crate) fn assert_and_project(x: MaybeRelocatable<QM31>) -> MaybeRelocatable<M31> {
-    match x {
-        MaybeRelocatable::Relocatable(x) => MaybeRelocatable::<M31>::Relocatable(x),
-        MaybeRelocatable::Absolute(x) => MaybeRelocatable::Absolute(assert_and_project_on_felt(x)),
-    }
-}
-
-impl Add<MaybeRelocatable<QM31>> for MaybeRelocatable<M31> {
+impl TryAdd<MaybeRelocatable<QM31>> for MaybeRelocatable<M31> {
     type Output = MaybeRelocatable<QM31>;
-    fn add(self, rhs: MaybeRelocatable<QM31>) -> MaybeRelocatable<QM31> {
-        match (self, rhs) {
+    fn try_add(self, rhs: MaybeRelocatable<QM31>) -> Result<Self::Output, RelocatableError> {
+        Ok(match (self, rhs) {
             (MaybeRelocatable::Relocatable(lhs), MaybeRelocatable::Absolute(rhs)) => {
-                MaybeRelocatable::Relocatable(lhs + assert_and_project_on_felt(rhs))
+                MaybeRelocatable::Relocatable(lhs + project_on_felt(rhs)?)
             }
             (MaybeRelocatable::Absolute(lhs), MaybeRelocatable::Relocatable(rhs)) => {
                 MaybeRelocatable::Relocatable(rhs + lhs)
             }
             (MaybeRelocatable::Relocatable(_), MaybeRelocatable::Relocatable(_)) => {
-                panic!("Cannot add two relocatables.")
+                return Err(RelocatableError::AddRelocatables)
             }
             (MaybeRelocatable::Absolute(lhs), MaybeRelocatable::Absolute(rhs)) => {
                 MaybeRelocatable::Absolute(lhs + rhs)
             }
-        }
+        })
     }
 }
 
-impl Add<MaybeRelocatable<M31>> for MaybeRelocatable<QM31> {
+impl TryAdd<MaybeRelocatable<M31>> for MaybeRelocatable<QM31> {
     type Output = Self;
-    fn add(self, rhs: MaybeRelocatable<M31>) -> Self {
-        match (self, rhs) {
+    fn try_add(self, rhs: MaybeRelocatable<M31>) -> Result<Self, RelocatableError> {
+        Ok(match (self, rhs) {
             (MaybeRelocatable::Relocatable(lhs), MaybeRelocatable::Absolute(rhs)) => {
                 MaybeRelocatable::Relocatable(lhs + rhs)
             }
             (MaybeRelocatable::Absolute(lhs), MaybeRelocatable::Relocatable(rhs)) => {
-                MaybeRelocatable::Relocatable(rhs + assert_and_project_on_felt(lhs))
+                MaybeRelocatable::Relocatable(rhs + project_on_felt(lhs)?)
             }
             (MaybeRelocatable::Relocatable(_), MaybeRelocatable::Relocatable(_)) => {
-                panic!("Cannot add two relocatables.")
+                return Err(RelocatableError::AddRelocatables)
             }
             (MaybeRelocatable::Absolute(lhs), MaybeRelocatable::Absolute(rhs)) => {
                 MaybeRelocatable::Absolute(lhs + rhs)
             }
-        }
+        })
     }
 }
 
-impl Add<MaybeRelocatable<QM31>> for MaybeRelocatable<QM31> {
+impl TryAdd for MaybeRelocatable<QM31> {
     type Output = Self;
-    fn add(self, rhs: Self) -> Self {
-        match (self, rhs) {
+    fn try_add(self, rhs: Self) -> Result<Self, RelocatableError> {
+        Ok(match (self, rhs) {
             (MaybeRelocatable::Relocatable(lhs), MaybeRelocatable::Absolute(rhs)) => {
-                MaybeRelocatable::Relocatable(lhs + assert_and_project_on_felt(rhs))
+                MaybeRelocatable::Relocatable(lhs + project_on_felt(rhs)?)
             }
             (MaybeRelocatable::Absolute(lhs), MaybeRelocatable::Relocatable(rhs)) => {
-                MaybeRelocatable::Relocatable(rhs + assert_and_project_on_felt(lhs))
+                MaybeRelocatable::Relocatable(rhs + project_on_felt(lhs)?)
             }
             (MaybeRelocatable::Relocatable(_), MaybeRelocatable::Relocatable(_)) => {
-                panic!("Cannot add two relocatables.")
+                return Err(RelocatableError::AddRelocatables)
             }
             (MaybeRelocatable::Absolute(lhs), MaybeRelocatable::Absolute(rhs)) => {
                 MaybeRelocatable::Absolute(lhs + rhs)
             }
-        }
+        })
     }
 }
 
+impl_op_with_try_op!(
+    Add,
+    add,
+    TryAdd,
+    try_add,
+    MaybeRelocatable<M31>,
+    MaybeRelocatable<M31>
+);
+impl_op_with_try_op!(
+    Add,
+    add,
+    TryAdd,
+    try_add,
+    MaybeRelocatable<QM31>,
+    MaybeRelocatable<M31>
+);
+impl_op_with_try_op!(
+    Add,
+    add,
+    TryAdd,
+    try_add,
+    MaybeRelocatable<QM31>,
+    MaybeRelocatable<QM31>
+);
+impl_op_with_try_op!(
+    Add,
+    add,
+    TryAdd,
+    try_add,
+    MaybeRelocatable<M31>,
+    MaybeRelocatable<QM31>
+);
+
 impl<T: Add<M31, Output = T> + From<M31>> Add<M31> for MaybeRelocatable<T> {
     type Output = Self;
     fn add(self, rhs: M31) -> Self {
@@ -207,98 +314,131 @@ impl Sub<M31> for Relocatable {
     }
 }
 
-impl Sub<MaybeRelocatable<M31>> for MaybeRelocatable<M31> {
+impl TrySub for MaybeRelocatable<M31> {
     type Output = Self;
-    fn sub(self, rhs: Self) -> Self {
-        match (self, rhs) {
+    fn try_sub(self, rhs: Self) -> Result<Self, RelocatableError> {
+        Ok(match (self, rhs) {
             (MaybeRelocatable::Relocatable(lhs), MaybeRelocatable::Absolute(rhs)) => {
                 MaybeRelocatable::Relocatable(lhs - rhs)
             }
             (MaybeRelocatable::Absolute(_), MaybeRelocatable::Relocatable(_)) => {
-                panic!("Cannot subtract a relocatable from an absolute.")
+                return Err(RelocatableError::SubRelocatableFromAbsolute)
             }
             (MaybeRelocatable::Relocatable(lhs), MaybeRelocatable::Relocatable(rhs)) => {
                 if lhs.segment != rhs.segment {
-                    panic!("Cannot subtract relocatables from different segments.");
+                    return Err(RelocatableError::SubDifferentSegments);
                 }
                 MaybeRelocatable::Absolute(lhs.offset - rhs.offset)
             }
             (MaybeRelocatable::Absolute(lhs), MaybeRelocatable::Absolute(rhs)) => {
                 MaybeRelocatable::Absolute(lhs - rhs)
             }
-        }
+        })
     }
 }
 
-impl Sub<MaybeRelocatable<QM31>> for MaybeRelocatable<M31> {
+impl TrySub<MaybeRelocatable<QM31>> for MaybeRelocatable<M31> {
     type Output = MaybeRelocatable<QM31>;
-    fn sub(self, rhs: MaybeRelocatable<QM31>) -> MaybeRelocatable<QM31> {
-        match (self, rhs) {
+    fn try_sub(self, rhs: MaybeRelocatable<QM31>) -> Result<Self::Output, RelocatableError> {
+        Ok(match (self, rhs) {
             (MaybeRelocatable::Relocatable(lhs), MaybeRelocatable::Absolute(rhs)) => {
-                MaybeRelocatable::Relocatable(lhs - assert_and_project_on_felt(rhs))
+                MaybeRelocatable::Relocatable(lhs - project_on_felt(rhs)?)
             }
             (MaybeRelocatable::Absolute(_), MaybeRelocatable::Relocatable(_)) => {
-                panic!("Cannot subtract a relocatable from an absolute.")
+                return Err(RelocatableError::SubRelocatableFromAbsolute)
             }
             (MaybeRelocatable::Relocatable(lhs), MaybeRelocatable::Relocatable(rhs)) => {
                 if lhs.segment != rhs.segment {
-                    panic!("Cannot subtract relocatables from different segments.");
+                    return Err(RelocatableError::SubDifferentSegments);
                 }
                 MaybeRelocatable::Absolute((lhs.offset - rhs.offset).into())
             }
             (MaybeRelocatable::Absolute(lhs), MaybeRelocatable::Absolute(rhs)) => {
                 MaybeRelocatable::Absolute(lhs - rhs)
             }
-        }
+        })
     }
 }
 
-impl Sub<MaybeRelocatable<M31>> for MaybeRelocatable<QM31> {
+impl TrySub<MaybeRelocatable<M31>> for MaybeRelocatable<QM31> {
     type Output = Self;
-    fn sub(self, rhs: MaybeRelocatable<M31>) -> Self {
-        match (self, rhs) {
+    fn try_sub(self, rhs: MaybeRelocatable<M31>) -> Result<Self, RelocatableError> {
+        Ok(match (self, rhs) {
             (MaybeRelocatable::Relocatable(lhs), MaybeRelocatable::Absolute(rhs)) => {
                 MaybeRelocatable::Relocatable(lhs - rhs)
             }
             (MaybeRelocatable::Absolute(_), MaybeRelocatable::Relocatable(_)) => {
-                panic!("Cannot subtract a relocatable from an absolute.")
+                return Err(RelocatableError::SubRelocatableFromAbsolute)
             }
             (MaybeRelocatable::Relocatable(lhs), MaybeRelocatable::Relocatable(rhs)) => {
                 if lhs.segment != rhs.segment {
-                    panic!("Cannot subtract relocatables from different segments.");
+                    return Err(RelocatableError::SubDifferentSegments);
                 }
                 MaybeRelocatable::Absolute(QM31::from(lhs.offset - rhs.offset))
             }
             (MaybeRelocatable::Absolute(lhs), MaybeRelocatable::Absolute(rhs)) => {
                 MaybeRelocatable::Absolute(lhs - rhs)
             }
-        }
+        })
     }
 }
 
-impl Sub<MaybeRelocatable<QM31>> for MaybeRelocatable<QM31> {
+impl TrySub for MaybeRelocatable<QM31> {
     type Output = Self;
-    fn sub(self, rhs: Self) -> Self {
-        match (self, rhs) {
+    fn try_sub(self, rhs: Self) -> Result<Self, RelocatableError> {
+        Ok(match (self, rhs) {
             (MaybeRelocatable::Relocatable(lhs), MaybeRelocatable::Absolute(rhs)) => {
-                MaybeRelocatable::Relocatable(lhs - assert_and_project_on_felt(rhs))
+                MaybeRelocatable::Relocatable(lhs - project_on_felt(rhs)?)
             }
             (MaybeRelocatable::Absolute(_), MaybeRelocatable::Relocatable(_)) => {
-                panic!("Cannot subtract a relocatable from an absolute.")
+                return Err(RelocatableError::SubRelocatableFromAbsolute)
             }
             (MaybeRelocatable::Relocatable(lhs), MaybeRelocatable::Relocatable(rhs)) => {
                 if lhs.segment != rhs.segment {
-                    panic!("Cannot subtract relocatables from different segments.");
+                    return Err(RelocatableError::SubDifferentSegments);
                 }
                 MaybeRelocatable::Absolute(QM31::from(lhs.offset - rhs.offset))
             }
             (MaybeRelocatable::Absolute(lhs), MaybeRelocatable::Absolute(rhs)) => {
                 MaybeRelocatable::Absolute(lhs - rhs)
             }
-        }
+        })
     }
 }
 
+impl_op_with_try_op!(
+    Sub,
+    sub,
+    TrySub,
+    try_sub,
+    MaybeRelocatable<M31>,
+    MaybeRelocatable<M31>
+);
+impl_op_with_try_op!(
+    Sub,
+    sub,
+    TrySub,
+    try_sub,
+    MaybeRelocatable<QM31>,
+    MaybeRelocatable<M31>
+);
+impl_op_with_try_op!(
+    Sub,
+    sub,
+    TrySub,
+    try_sub,
+    MaybeRelocatable<QM31>,
+    MaybeRelocatable<QM31>
+);
+impl_op_with_try_op!(
+    Sub,
+    sub,
+    TrySub,
+    try_sub,
+    MaybeRelocatable<M31>,
+    MaybeRelocatable<QM31>
+);
+
 impl<T: Sub<M31, Output = T> + From<M31>> Sub<M31> for MaybeRelocatable<T> {
     type Output = Self;
     fn sub(self, rhs: M31) -> Self {
@@ -309,54 +449,86 @@ impl<T: Sub<M31, Output = T> + From<M31>> Sub<M31> for MaybeRelocatable<T> {
     }
 }
 
-impl<T: From<M31> + Mul<S, Output = S>, S: From<M31>> Mul<MaybeRelocatable<S>>
+impl<T: From<M31> + Mul<S, Output = S>, S: From<M31>> TryMul<MaybeRelocatable<S>>
     for MaybeRelocatable<T>
 {
     type Output = MaybeRelocatable<S>;
-    fn mul(self, rhs: MaybeRelocatable<S>) -> Self::Output {
+    fn try_mul(self, rhs: MaybeRelocatable<S>) -> Result<Self::Output, RelocatableError> {
         match (self, rhs) {
             (MaybeRelocatable::Relocatable(_), _) | (_, MaybeRelocatable::Relocatable(_)) => {
-                panic!("Multiplication involving relocatable values is not possible.")
+                Err(RelocatableError::MulRelocatable)
             }
             (MaybeRelocatable::Absolute(lhs), MaybeRelocatable::Absolute(rhs)) => {
-                MaybeRelocatable::Absolute(lhs * rhs)
+                Ok(MaybeRelocatable::Absolute(lhs * rhs))
             }
         }
     }
 }
 
-impl<T: From<M31> + Mul<M31, Output = T>> Mul<M31> for MaybeRelocatable<T> {
+impl<T: From<M31> + Mul<S, Output = S>, S: From<M31>> Mul<MaybeRelocatable<S>>
+    for MaybeRelocatable<T>
+{
+    type Output = MaybeRelocatable<S>;
+    fn mul(self, rhs: MaybeRelocatable<S>) -> Self::Output {
+        self.try_mul(rhs).unwrap_or_else(|err| panic!("{err}"))
+    }
+}
+
+impl<T: From<M31> + Mul<M31, Output = T>> TryMul<M31> for MaybeRelocatable<T> {
     type Output = Self;
-    fn mul(self, rhs: M31) -> Self {
+    fn try_mul(self, rhs: M31) -> Result<Self, RelocatableError> {
         match self {
-            MaybeRelocatable::Relocatable(_) => panic!("Cannot multiply a relocatable."),
-            MaybeRelocatable::Absolute(lhs) => MaybeRelocatable::Absolute(lhs * rhs),
+            MaybeRelocatable::Relocatable(_) => Err(RelocatableError::MulRelocatable),
+            MaybeRelocatable::Absolute(lhs) => Ok(MaybeRelocatable::Absolute(lhs * rhs)),
         }
     }
 }
 
-impl<T: From<M31> + Div<S, Output = S>, S: From<M31>> Div<MaybeRelocatable<S>>
+impl<T: From<M31> + Mul<M31, Output = T>> Mul<M31> for MaybeRelocatable<T> {
+    type Output = Self;
+    fn mul(self, rhs: M31) -> Self {
+        self.try_mul(rhs).unwrap_or_else(|err| panic!("{err}"))
+    }
+}
+
+impl<T: From<M31> + Div<S, Output = S>, S: From<M31>> TryDiv<MaybeRelocatable<S>>
     for MaybeRelocatable<T>
 {
     type Output = MaybeRelocatable<S>;
-    fn div(self, rhs: MaybeRelocatable<S>) -> Self::Output {
+    fn try_div(self, rhs: MaybeRelocatable<S>) -> Result<Self::Output, RelocatableError> {
         match (self, rhs) {
             (MaybeRelocatable::Relocatable(_), _) | (_, MaybeRelocatable::Relocatable(_)) => {
-                panic!("Division involving relocatable values is not possible.")
+                Err(RelocatableError::DivRelocatable)
             }
             (MaybeRelocatable::Absolute(lhs), MaybeRelocatable::Absolute(rhs)) => {
-                MaybeRelocatable::Absolute(lhs / rhs)
+                Ok(MaybeRelocatable::Absolute(lhs / rhs))
             }
         }
     }
 }
 
-impl<T: From<M31> + Div<M31, Output = T>> Div<M31> for MaybeRelocatable<T> {
+impl<T: From<M31> + Div<S, Output = S>, S: From<M31>> Div<MaybeRelocatable<S>>
+    for MaybeRelocatable<T>
+{
+    type Output = MaybeRelocatable<S>;
+    fn div(self, rhs: MaybeRelocatable<S>) -> Self::Output {
+        self.try_div(rhs).unwrap_or_else(|err| panic!("{err}"))
+    }
+}
+
+impl<T: From<M31> + Div<M31, Output = T>> TryDiv<M31> for MaybeRelocatable<T> {
     type Output = Self;
-    fn div(self, rhs: M31) -> Self {
+    fn try_div(self, rhs: M31) -> Result<Self, RelocatableError> {
         match self {
-            MaybeRelocatable::Relocatable(_) => panic!("Cannot divide a relocatable."),
-            MaybeRelocatable::Absolute(lhs) => MaybeRelocatable::Absolute(lhs / rhs),
+            MaybeRelocatable::Relocatable(_) => Err(RelocatableError::DivRelocatable),
+            MaybeRelocatable::Absolute(lhs) => Ok(MaybeRelocatable::Absolute(lhs / rhs)),
         }
     }
 }
+
+impl<T: From<M31> + Div<M31, Output = T>> Div<M31> for MaybeRelocatable<T> {
+    type Output = Self;
+    fn div(self, rhs: M31) -> Self {
+        self.try_div(rhs).unwrap_or_else(|err| panic!("{err}"))
+    }
+}
@@ -0,0 +1,132 @@
+use serde::{Deserialize, Serialize};
+use stwo_prover::core::fields::m31::M31;
+
+use super::relocatable::{Relocatable, RelocationTable, Segment};
+use super::{Memory, MemoryError};
+use crate::utils::u32_from_usize;
+
+/// What a memory segment is used for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SegmentKind {
+    Program,
+    Execution,
+    Output,
+    /// A segment holding a value of the program input, written by a hint.
+    Input,
+    /// A fake segment used as the `fp` or `pc` to return to from the entry point.
+    ReturnTarget,
+}
+
+impl Memory {
+    /// Adds a new segment, and returns its base address.
+    pub fn add_segment(&mut self, kind: SegmentKind) -> Relocatable {
+        let segment = self.segment_kinds.len();
+        self.segment_kinds.push(kind);
+        self.segment_sizes.push(None);
+
+        Relocatable::from((segment, 0))
+    }
+
+    /// The kinds of the added segments, by segment index.
+    pub fn segment_kinds(&self) -> &[SegmentKind] {
+        &self.segment_kinds
+    }
+
+    /// The number of cells up to and including the last written cell in `segment`.
+    ///
+    /// Only meaningful before relocation, which moves all the cells to absolute addresses.
+    pub fn used_size(&self, segment: Segment) -> usize {
+        self.relocatable_data
+            .get(segment)
+            .map_or(0, |cells| cells.len())
+    }
+
+    /// The number of written cells in `segment`.
+    ///
+    /// Only meaningful before relocation, which moves all the cells to absolute addresses.
+    pub fn n_written(&self, segment: Segment) -> usize {
+        self.relocatable_data
+            .get(segment)
+            .map_or(0, |cells| cells.n_written())
+    }
+
+    /// The size of `segment` in the relocated memory: its finalized size if it was finalized, and
+    /// its used size otherwise. Segments are finalized to their used size on relocation.
+    pub fn segment_size(&self, segment: Segment) -> usize {
+        self.segment_sizes
+            .get(segment)
+            .copied()
+            .flatten()
+            .unwrap_or_else(|| self.used_size(segment))
+    }
+
+    /// Fixes the size of `segment` in the relocated memory, e.g. to reserve cells that are never
+    /// written. Fails if the segment was not added.
+    pub fn finalize_segment(&mut self, segment: Segment, size: usize) -> Result<(), MemoryError> {
+        let segment_size = self
+            .segment_sizes
+            .get_mut(segment)
+            .ok_or(MemoryError::UnknownSegment(segment))?;
+        *segment_size = Some(size);
+
+        Ok(())
+    }
+
+    /// Returns a table that lays the added segments out contiguously, in order, starting at
+    /// address 1.
+    pub fn relocation_table(&self) -> RelocationTable {
+        let mut base = 1;
+        (0..self.segment_kinds.len())
+            .map(|segment| {
+                let entry = (segment, M31(base));
+                base += u32_from_usize(self.segment_size(segment));
+                entry
+            })
+            .collect()
+    }
+
+    /// Finalizes the size of every segment that was not finalized yet to its used size.
+    pub(super) fn finalize_segments(&mut self) {
+        for segment in 0..self.segment_kinds.len() {
+            self.segment_sizes[segment] = Some(self.segment_size(segment));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use num_traits::Zero;
+    use stwo_prover::core::fields::qm31::QM31;
+
+    use super::*;
+
+    #[test]
+    fn test_segments() {
+        let mut memory = Memory::default();
+        let program = memory.add_segment(SegmentKind::Program);
+        let execution = memory.add_segment(SegmentKind::Execution);
+        let output = memory.add_segment(SegmentKind::Output);
+        memory.insert(program + M31(2), QM31::zero()).unwrap();
+        memory.insert(execution, QM31::zero()).unwrap();
+        memory.finalize_segment(output.segment, 3).unwrap();
+
+        assert_eq!(
+            memory.segment_kinds(),
+            [
+                SegmentKind::Program,
+                SegmentKind::Execution,
+                SegmentKind::Output
+            ]
+        );
+        assert_eq!(memory.used_size(program.segment), 3);
+        assert_eq!(memory.used_size(output.segment), 0);
+        assert_eq!(memory.segment_size(output.segment), 3);
+
+        let table = memory.relocation_table();
+
+        assert_eq!(table[&program.segment], M31(1));
+        assert_eq!(table[&execution.segment], M31(4));
+        assert_eq!(table[&output.segment], M31(5));
+    }
+}
@@ -0,0 +1,32 @@
+//! Helpers shared by the tests of this crate and of its dependents, behind the `test-utils`
+//! feature.
+
+use std::path::PathBuf;
+
+use crate::vm::hints::HintRunner;
+use crate::vm::{Input, Program, VM};
+
+fn get_tests_data_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("data")
+}
+
+/// The compiled fibonacci program from the tests data.
+pub fn fibonacci_program() -> Program {
+    Program::from_compiled_file(get_tests_data_dir().join("fibonacci_compiled.json")).unwrap()
+}
+
+/// The input of the fibonacci program, claiming the fibonacci number at `index`.
+pub fn fibonacci_input(index: u32) -> Input {
+    serde_json::from_value(serde_json::json!({
+        "fibonacci_claim_index": [format!("{index:#x}"), "0x0", "0x0", "0x0"]
+    }))
+    .unwrap()
+}
+
+/// A VM running the fibonacci program from `main`, claiming the fibonacci number at `index`.
+pub fn fibonacci_vm(index: u32) -> VM {
+    VM::create_for_main_entry_point(fibonacci_program(), HintRunner::new(fibonacci_input(index)))
+        .unwrap()
+}
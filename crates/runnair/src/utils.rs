@@ -1,17 +1,14 @@
+#[cfg(test)]
 use std::path::PathBuf;
 
-use stwo_prover::core::fields::m31::M31;
-use stwo_prover::core::fields::qm31::QM31;
+use stwo_prover::core::fields::m31::{M31, P};
 
 // Converters.
 
-pub(crate) fn m31_from_hex_str(x: &str) -> M31 {
-    M31(u32::from_str_radix(x.trim_start_matches("0x"), 16).unwrap())
-}
-
-pub(crate) fn qm31_from_hex_str_array(x: [&str; 4]) -> QM31 {
-    let m31_array = x.map(m31_from_hex_str);
-    QM31::from_m31_array(m31_array)
+/// Parses a hex string (with or without a `0x` prefix) as a reduced base field element.
+pub(crate) fn m31_from_hex_str(x: &str) -> Option<M31> {
+    let value = u32::from_str_radix(x.trim_start_matches("0x"), 16).ok()?;
+    (value < P).then_some(M31(value))
 }
 
 pub(crate) fn u32_from_usize(value: usize) -> u32 {
@@ -33,11 +30,18 @@ pub(crate) fn maybe_resize<T: Clone>(vector: &mut Vec<T>, index: usize, default_
     }
 }
 
-pub(crate) fn get_crate_dir() -> PathBuf {
-    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-    manifest_dir.to_path_buf()
-}
+/// Creates an empty directory under the system temp dir, unique to this process and call, so that
+/// tests running in parallel or in several processes don't share files.
+#[cfg(test)]
+pub(crate) fn create_temp_dir(name: &str) -> PathBuf {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static N_DIRS: AtomicUsize = AtomicUsize::new(0);
+    let index = N_DIRS.fetch_add(1, Ordering::Relaxed);
+    let dir = std::env::temp_dir().join(format!("runnair_{name}_{}_{index}", std::process::id()));
+    // Left over by an earlier process with the same id.
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
 
-pub(crate) fn get_tests_data_dir() -> PathBuf {
-    get_crate_dir().join("tests").join("data")
+    dir
 }
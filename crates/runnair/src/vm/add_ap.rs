@@ -1,47 +1,21 @@
-use paste::paste;
 use stwo_prover::core::fields::m31::M31;
 
-use crate::memory::relocatable::assert_and_project;
+use crate::memory::relocatable::TryAdd;
 use crate::memory::{MaybeRelocatableAddr, Memory};
-use crate::vm::{InstructionArgs, State};
+use crate::vm::opcode::Operand;
+use crate::vm::{operand, InstructionArgs, InstructionResult, State};
 
-fn addap(state: State, summand: MaybeRelocatableAddr) -> State {
-    State {
-        ap: state.ap + summand,
+/// `ap += operand`.
+pub(crate) fn addap(
+    memory: &Memory,
+    state: State,
+    operand: Operand,
+    args: &InstructionArgs,
+) -> InstructionResult {
+    let summand = MaybeRelocatableAddr::try_from(operand::read(memory, state, operand, args)?)?;
+    Ok(State {
+        ap: state.ap.try_add(summand)?,
         fp: state.fp,
         pc: state.pc + M31(1),
-    }
+    })
 }
-
-macro_rules! addap_with_operand {
-    ($operand:ident) => {
-        paste! {
-            pub(crate) fn [<addap_ $operand>](
-                memory: &mut Memory,
-                state: State,
-                args: InstructionArgs,
-            ) -> State {
-                let summand = crate::vm::operand::$operand(memory, state, &args);
-                addap(state, assert_and_project(summand))
-            }
-        }
-    };
-}
-
-addap_with_operand!(add_ap_ap);
-addap_with_operand!(add_ap_fp);
-addap_with_operand!(add_fp_ap);
-addap_with_operand!(add_fp_fp);
-addap_with_operand!(add_imm_ap);
-addap_with_operand!(add_imm_fp);
-addap_with_operand!(deref_ap);
-addap_with_operand!(deref_fp);
-addap_with_operand!(double_deref_ap);
-addap_with_operand!(double_deref_fp);
-addap_with_operand!(imm);
-addap_with_operand!(mul_ap_ap);
-addap_with_operand!(mul_ap_fp);
-addap_with_operand!(mul_fp_ap);
-addap_with_operand!(mul_fp_fp);
-addap_with_operand!(mul_imm_ap);
-addap_with_operand!(mul_imm_fp);
@@ -1,7 +1,14 @@
-use paste::paste;
+use num_traits::Zero;
 use stwo_prover::core::fields::m31::M31;
+use stwo_prover::core::fields::qm31::QM31;
 
+use crate::memory::relocatable::{
+    MaybeRelocatable, RelocatableError, TryAdd, TryDiv, TryMul, TrySub,
+};
 use crate::memory::{MaybeRelocatableValue, Memory};
+use crate::vm::deref::{assign_or_assert_deref, assign_or_assert_double_deref};
+use crate::vm::error::InstructionError;
+use crate::vm::opcode::{Operand, Register};
 use crate::vm::{resolve_addresses, InstructionArgs, State};
 
 enum Operation {
@@ -14,34 +21,49 @@ impl Operation {
         self,
         x: MaybeRelocatableValue,
         y: impl Into<MaybeRelocatableValue>,
-    ) -> MaybeRelocatableValue {
+    ) -> Result<MaybeRelocatableValue, RelocatableError> {
         match self {
-            Operation::Add => x + y.into(),
-            Operation::Mul => x * y.into(),
+            Operation::Add => x.try_add(y.into()),
+            Operation::Mul => x.try_mul(y.into()),
         }
     }
 
+    /// Solves `x = y op z` for `z`.
     fn deduce(
         self,
         x: MaybeRelocatableValue,
         y: impl Into<MaybeRelocatableValue>,
-    ) -> MaybeRelocatableValue {
+    ) -> Result<MaybeRelocatableValue, InstructionError> {
+        let y = y.into();
         match self {
-            Operation::Add => x - y.into(),
-            Operation::Mul => x / y.into(),
+            Operation::Add => Ok(x.try_sub(y)?),
+            Operation::Mul if y == MaybeRelocatable::Absolute(QM31::zero()) => Err(
+                InstructionError::DeductionFailed("Cannot deduce an operand multiplied by zero."),
+            ),
+            Operation::Mul => Ok(x.try_div(y)?),
         }
     }
 }
 
+/// Asserts that a known destination value equals the value computed from the operands.
+fn assert_equal(
+    expected: MaybeRelocatableValue,
+    actual: MaybeRelocatableValue,
+) -> Result<(), InstructionError> {
+    if expected != actual {
+        return Err(InstructionError::AssertionFailed { expected, actual });
+    }
+    Ok(())
+}
+
 fn assign_or_assert_operation(
     memory: &mut Memory,
     state: State,
     operation: Operation,
-    bases: &[&str; 3],
+    bases: &[Register; 3],
     args: &[M31; 3],
-) {
-    let [dest, op1, op2] = bases;
-    let [dest_addr, op1_addr, op2_addr] = resolve_addresses(state, &[dest, op1, op2], args);
+) -> Result<(), InstructionError> {
+    let [dest_addr, op1_addr, op2_addr] = resolve_addresses(state, bases, args);
 
     match (
         memory.get(dest_addr),
@@ -49,229 +71,103 @@ fn assign_or_assert_operation(
         memory.get(op2_addr),
     ) {
         (Some(dest_val), Some(op1_val), Some(op2_val)) => {
-            assert_eq!(
-                dest_val,
-                operation.apply(op1_val, op2_val),
-                "Assertion failed."
-            );
+            assert_equal(dest_val, operation.apply(op1_val, op2_val)?)?;
         }
         (None, Some(op1_val), Some(op2_val)) => {
-            memory.insert(dest_addr, operation.apply(op1_val, op2_val));
+            memory.insert(dest_addr, operation.apply(op1_val, op2_val)?)?;
         }
         (Some(dest_val), None, Some(op2_val)) => {
-            memory.insert(op1_addr, operation.deduce(dest_val, op2_val));
+            memory.insert(op1_addr, operation.deduce(dest_val, op2_val)?)?;
         }
         (Some(dest_val), Some(op1_val), None) => {
-            memory.insert(op2_addr, operation.deduce(dest_val, op1_val));
+            memory.insert(op2_addr, operation.deduce(dest_val, op1_val)?)?;
         }
-        _ => panic!("Cannot deduce more than one operand"),
-    };
-}
-
-// TODO: handle mul.
-macro_rules! define_assert {
-    ($dest:ident, $op1:ident, $op2:ident) => {
-        paste! {
-            /// Assert add without incrementing `ap`: `assert_[ap/fp]_add_[ap/fp]_[ap/fp]`.
-            pub(crate) fn [<assert_ $dest _add_ $op1 _ $op2>] (
-                memory: &mut Memory,
-                state: State,
-                args: InstructionArgs,
-            ) -> State {
-                let (dest, op1, op2) = (stringify!($dest), stringify!($op1), stringify!($op2));
-                assign_or_assert_operation(memory, state, Operation::Add, &[dest, op1, op2], &args);
-                state.advance()
-            }
-
-            /// Assert add with incrementing `ap`: `assert_[ap/fp]_add_[ap/fp]_[ap/fp][_appp]`.
-            pub(crate) fn [<assert_ $dest _add_ $op1 _ $op2 _appp>] (
-                memory: &mut Memory,
-                state: State,
-                args: InstructionArgs,
-            ) -> State {
-                let (dest, op1, op2) = (stringify!($dest), stringify!($op1), stringify!($op2));
-                assign_or_assert_operation(memory, state, Operation::Add, &[dest, op1, op2], &args);
-                state.advance_and_increment_ap()
-            }
-
-            /// Assert mul without incrementing `ap`: `assert_[ap/fp]_mul_[ap/fp]_[ap/fp]`.
-            pub(crate) fn [<assert_ $dest _mul_ $op1 _ $op2>] (
-                memory: &mut Memory,
-                state: State,
-                args: InstructionArgs,
-            ) -> State {
-                let (dest, op1, op2) = (stringify!($dest), stringify!($op1), stringify!($op2));
-                assign_or_assert_operation(memory, state, Operation::Mul, &[dest, op1, op2], &args);
-                state.advance()
-            }
-
-            /// Assert mul with incrementing `ap`: `assert_[ap/fp]_mul_[ap/fp]_[ap/fp][_appp]`.
-            pub(crate) fn [<assert_ $dest _mul_ $op1 _ $op2 _appp>] (
-                memory: &mut Memory,
-                state: State,
-                args: InstructionArgs,
-            ) -> State {
-                let (dest, op1, op2) = (stringify!($dest), stringify!($op1), stringify!($op2));
-                assign_or_assert_operation(memory, state, Operation::Mul, &[dest, op1, op2], &args);
-                state.advance_and_increment_ap()
-            }
+        _ => {
+            return Err(InstructionError::DeductionFailed(
+                "Cannot deduce more than one operand.",
+            ))
         }
     };
+
+    Ok(())
 }
 
 fn assign_or_assert_operation_with_imm(
     memory: &mut Memory,
     state: State,
     operation: Operation,
-    bases: &[&str; 2],
+    bases: &[Register; 2],
     args: &[M31; 3],
-) {
-    let [dest, op1] = bases;
-    let [dest_addr, op1_addr] = resolve_addresses(state, &[dest, op1], &[args[0], args[2]]);
+) -> Result<(), InstructionError> {
+    let [dest_addr, op1_addr] = resolve_addresses(state, bases, &[args[0], args[2]]);
     let immediate = args[1];
 
     match (memory.get(dest_addr), memory.get(op1_addr)) {
         (Some(dest_val), Some(op1_val)) => {
-            assert_eq!(
-                dest_val,
-                operation.apply(op1_val, immediate),
-                "Assertion failed."
-            );
+            assert_equal(dest_val, operation.apply(op1_val, immediate)?)?;
         }
         (None, Some(op1_val)) => {
-            memory.insert(dest_addr, operation.apply(op1_val, immediate));
+            memory.insert(dest_addr, operation.apply(op1_val, immediate)?)?;
         }
         (Some(dest_val), None) => {
-            memory.insert(op1_addr, operation.deduce(dest_val, immediate));
+            memory.insert(op1_addr, operation.deduce(dest_val, immediate)?)?;
         }
-        _ => panic!("Cannot deduce more than one operand"),
-    };
-}
-
-macro_rules! define_assert_with_imm {
-    ($dest:ident, $op1:ident) => {
-        paste! {
-            /// Assert add without incrementing `ap`: `assert_[ap/fp]_add_imm_[ap/fp]`.
-            pub(crate) fn [<assert_ $dest _add_imm_ $op1>] (
-                memory: &mut Memory,
-                state: State,
-                args: InstructionArgs,
-            ) -> State {
-                let (dest, op1) = (stringify!($dest), stringify!($op1));
-                assign_or_assert_operation_with_imm(
-                    memory,
-                    state,
-                    Operation::Add,
-                    &[dest, op1],
-                    &args,
-                );
-                state.advance()
-            }
-
-            /// Assert add with incrementing `ap`: `assert_[ap/fp]_add_imm_[ap/fp][_appp]`.
-            pub(crate) fn [<assert_ $dest _add_imm_ $op1 _appp>] (
-                memory: &mut Memory,
-                state: State,
-                args: InstructionArgs,
-            ) -> State {
-                let (dest, op1) = (stringify!($dest), stringify!($op1));
-                assign_or_assert_operation_with_imm(
-                    memory,
-                    state,
-                    Operation::Add,
-                    &[dest, op1],
-                    &args,
-                );
-                state.advance_and_increment_ap()
-            }
-
-            /// Assert mul without incrementing `ap`: `assert_[ap/fp]_mul_imm_[ap/fp]`.
-            pub(crate) fn [<assert_ $dest _mul_imm_ $op1>] (
-                memory: &mut Memory,
-                state: State,
-                args: InstructionArgs,
-            ) -> State {
-                let (dest, op1) = (stringify!($dest), stringify!($op1));
-                assign_or_assert_operation_with_imm(
-                    memory,
-                    state,
-                    Operation::Mul,
-                    &[dest, op1],
-                    &args,
-                );
-                state.advance()
-            }
-
-            /// Assert mul with incrementing `ap`: `assert_[ap/fp]_mul_imm_[ap/fp][_appp]`.
-            pub(crate) fn [<assert_ $dest _mul_imm_ $op1 _appp>] (
-                memory: &mut Memory,
-                state: State,
-                args: InstructionArgs,
-            ) -> State {
-                let (dest, op1) = (stringify!($dest), stringify!($op1));
-                assign_or_assert_operation_with_imm(
-                    memory,
-                    state,
-                    Operation::Mul,
-                    &[dest, op1],
-                    &args,
-                );
-                state.advance_and_increment_ap()
-            }
+        _ => {
+            return Err(InstructionError::DeductionFailed(
+                "Cannot deduce more than one operand.",
+            ))
         }
     };
+
+    Ok(())
 }
 
-fn assign_or_assert_imm(memory: &mut Memory, state: State, base: &str, offsets: &[M31; 2]) {
+fn assign_or_assert_imm(
+    memory: &mut Memory,
+    state: State,
+    base: Register,
+    offsets: &[M31; 2],
+) -> Result<(), InstructionError> {
     let [dest_addr] = resolve_addresses(state, &[base], &[offsets[0]]);
     let immediate = MaybeRelocatableValue::Absolute(offsets[1].into());
 
     if let Some(dest_val) = memory.get(dest_addr) {
-        assert_eq!(dest_val, immediate, "Assertion failed.");
+        assert_equal(dest_val, immediate)?;
     } else {
-        memory.insert(dest_addr, immediate);
+        memory.insert(dest_addr, immediate)?;
     };
-}
 
-macro_rules! define_assert_imm {
-    ($dest:ident) => {
-        paste! {
-            /// Assert immediate without incrementing `ap`: `assert_[ap/fp]_imm`.
-            pub(crate) fn [<assert_ $dest _imm>] (
-                memory: &mut Memory,
-                state: State,
-                args: InstructionArgs,
-            ) -> State {
-                assign_or_assert_imm(memory, state, stringify!($dest), &[args[0], args[1]]);
-                state.advance()
-            }
+    Ok(())
+}
 
-            /// Assert immediate with incrementing `ap`: `assert_[ap/fp]_imm_appp`.
-            pub(crate) fn [<assert_ $dest _imm_appp>] (
-                memory: &mut Memory,
-                state: State,
-                args: InstructionArgs,
-            ) -> State {
-                assign_or_assert_imm(memory, state, stringify!($dest), &[args[0], args[1]]);
-                state.advance_and_increment_ap()
-            }
+/// `[dest+args[0]] = operand`, with the operand arguments starting at `args[1]`: asserts the
+/// equality if both sides are known, and otherwise deduces the unknown cell.
+pub(crate) fn assert(
+    memory: &mut Memory,
+    state: State,
+    dest: Register,
+    operand: Operand,
+    args: &InstructionArgs,
+) -> Result<(), InstructionError> {
+    match operand {
+        Operand::Imm => assign_or_assert_imm(memory, state, dest, &[args[0], args[1]]),
+        Operand::Deref(op1) => {
+            assign_or_assert_deref(memory, state, &[dest, op1], &[args[0], args[1]])
         }
-    };
+        Operand::DoubleDeref(op1) => {
+            assign_or_assert_double_deref(memory, state, &[dest, op1], args)
+        }
+        Operand::Add(op1, op2) => {
+            assign_or_assert_operation(memory, state, Operation::Add, &[dest, op1, op2], args)
+        }
+        Operand::AddImm(op1) => {
+            assign_or_assert_operation_with_imm(memory, state, Operation::Add, &[dest, op1], args)
+        }
+        Operand::Mul(op1, op2) => {
+            assign_or_assert_operation(memory, state, Operation::Mul, &[dest, op1, op2], args)
+        }
+        Operand::MulImm(op1) => {
+            assign_or_assert_operation_with_imm(memory, state, Operation::Mul, &[dest, op1], args)
+        }
+    }
 }
-
-define_assert!(ap, ap, ap);
-define_assert!(ap, ap, fp);
-define_assert!(ap, fp, ap);
-define_assert!(ap, fp, fp);
-define_assert!(fp, ap, ap);
-define_assert!(fp, ap, fp);
-define_assert!(fp, fp, ap);
-define_assert!(fp, fp, fp);
-define_assert_with_imm!(ap, ap);
-define_assert_with_imm!(ap, fp);
-define_assert_with_imm!(fp, ap);
-define_assert_with_imm!(fp, fp);
-define_assert_imm!(ap);
-define_assert_imm!(fp);
-
-// TODO: add tests.
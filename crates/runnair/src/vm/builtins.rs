@@ -0,0 +1,164 @@
+use serde::{Deserialize, Serialize};
+use stwo_prover::core::fields::m31::M31;
+use stwo_prover::core::fields::qm31::QM31;
+use thiserror::Error;
+
+use crate::memory::relocatable::{MaybeRelocatable, Relocatable};
+use crate::memory::segments::SegmentKind;
+use crate::memory::{MaybeRelocatableValue, Memory};
+use crate::utils::{u32_from_usize, usize_from_u32};
+
+/// A builtin listed in the `builtins` field of a compiled program.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Builtin {
+    Output,
+}
+
+impl Builtin {
+    /// The name of the builtin, as listed in compiled programs.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Output => "output",
+        }
+    }
+
+    pub fn segment_kind(self) -> SegmentKind {
+        match self {
+            Self::Output => SegmentKind::Output,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum OutputError {
+    #[error("The program does not use the output builtin.")]
+    NoOutputBuiltin,
+    #[error("Invalid final output pointer: {0:?}.")]
+    InvalidPointer(Option<MaybeRelocatableValue>),
+    #[error("Output cell {0} was not written.")]
+    MissingCell(usize),
+    #[error("Output cell {0} holds a relocatable value.")]
+    RelocatableCell(usize),
+    #[error("The output has {size} cells, but {used_size} cells were written.")]
+    WrittenPastEnd { size: usize, used_size: usize },
+}
+
+/// Reads the output segment starting at `base`, up to the final output pointer `end`.
+pub(crate) fn read_output(
+    memory: &Memory,
+    base: Relocatable,
+    end: Option<MaybeRelocatableValue>,
+) -> Result<Vec<QM31>, OutputError> {
+    let size = match end {
+        Some(MaybeRelocatable::Relocatable(end)) if end.segment == base.segment => {
+            usize_from_u32(end.offset.0)
+        }
+        _ => return Err(OutputError::InvalidPointer(end)),
+    };
+    let used_size = memory.used_size(base.segment);
+    if used_size > size {
+        return Err(OutputError::WrittenPastEnd { size, used_size });
+    }
+
+    (0..size)
+        .map(
+            |offset| match memory.get(base + M31(u32_from_usize(offset))) {
+                Some(MaybeRelocatable::Absolute(value)) => Ok(value),
+                Some(MaybeRelocatable::Relocatable(_)) => Err(OutputError::RelocatableCell(offset)),
+                None => Err(OutputError::MissingCell(offset)),
+            },
+        )
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{fibonacci_program, fibonacci_vm};
+    use crate::vm::hints::HintRunner;
+    use crate::vm::{Input, Program, VM};
+
+    #[test]
+    fn test_fibonacci_output() {
+        assert_eq!(fibonacci_program().builtins, [Builtin::Output]);
+        let mut vm = fibonacci_vm(5);
+        vm.execute().unwrap();
+
+        let output = vm.output().unwrap();
+
+        assert_eq!(output.len(), 2);
+        assert_eq!(output[0], M31(5).into());
+    }
+
+    #[test]
+    fn test_output_with_return_values() {
+        // `func main{output_ptr}() -> (res: felt)`: `[ap] = 7, ap++; [ap - 1] = [[fp - 3]];
+        // [ap] = [fp - 3] + 1, ap++; [ap] = 42, ap++; ret`.
+        let program = serde_json::json!({
+            "data": [
+                ["0x26", "0x0", "0x7", "0x0"],
+                ["0x23", "0x7ffffffe", "0x7ffffffc", "0x0"],
+                ["0x1c", "0x0", "0x1", "0x7ffffffc"],
+                ["0x26", "0x0", "0x2a", "0x0"],
+                ["0xab", "0x0", "0x0", "0x0"],
+            ],
+            "hints": {},
+            "builtins": ["output"],
+            "identifiers": {
+                "__main__.main": { "pc": 0, "type": "function" },
+                "__main__.main.ImplicitArgs": { "size": 1, "type": "struct" },
+                "__main__.main.Return": { "cairo_type": "(res: felt)", "type": "type_definition" },
+            },
+        });
+        let program: Program = serde_json::from_value(program).unwrap();
+        assert_eq!(program.return_sizes["__main__.main"], 2);
+        let json = serde_json::to_value(&program).unwrap();
+        let loaded: Program = serde_json::from_value(json).unwrap();
+        assert_eq!(loaded.return_sizes, program.return_sizes);
+        let mut vm =
+            VM::create_for_main_entry_point(program, HintRunner::new(Input::default())).unwrap();
+        vm.execute().unwrap();
+
+        assert_eq!(vm.output(), Ok(vec![M31(7).into()]));
+        assert_eq!(vm.return_values(1).unwrap(), [M31(42).into()]);
+    }
+
+    #[test]
+    fn test_no_output_builtin() {
+        // `ret`.
+        let program = Program::from_instructions(&[[171, 0, 0, 0]]);
+        let mut vm =
+            VM::create_for_main_entry_point(program, HintRunner::new(Input::default())).unwrap();
+        vm.execute().unwrap();
+
+        assert_eq!(vm.output(), Err(OutputError::NoOutputBuiltin));
+    }
+
+    #[test]
+    fn test_unsupported_builtin() {
+        let program = serde_json::json!({ "data": [], "hints": {}, "builtins": ["range_check"] });
+
+        assert!(serde_json::from_value::<Program>(program).is_err());
+    }
+
+    #[test]
+    fn test_non_contiguous_output() {
+        let mut memory = Memory::default();
+        let base = memory.add_segment(SegmentKind::Output);
+        memory.insert(base, M31(1)).unwrap();
+        memory.insert(base + M31(2), M31(3)).unwrap();
+
+        assert_eq!(
+            read_output(&memory, base, Some((base + M31(3)).into())),
+            Err(OutputError::MissingCell(1))
+        );
+        assert_eq!(
+            read_output(&memory, base, Some((base + M31(1)).into())),
+            Err(OutputError::WrittenPastEnd {
+                size: 1,
+                used_size: 3
+            })
+        );
+    }
+}
@@ -0,0 +1,43 @@
+use std::fmt::{self, Display};
+
+use serde::{Deserialize, Serialize};
+
+/// The source location of an instruction, from `debug_info.instruction_locations` in a compiled
+/// program.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct InstructionLocation {
+    /// The scopes accessible from the instruction, innermost last (e.g. `__main__.main`).
+    pub accessible_scopes: Vec<String>,
+    pub inst: Location,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Location {
+    pub input_file: InputFile,
+    pub start_line: usize,
+    pub start_col: usize,
+    pub end_line: usize,
+    pub end_col: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct InputFile {
+    pub filename: Option<String>,
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let filename = self.input_file.filename.as_deref().unwrap_or("<unknown>");
+        write!(f, "{filename}:{}:{}", self.start_line, self.start_col)
+    }
+}
+
+impl Display for InstructionLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.inst)?;
+        if !self.accessible_scopes.is_empty() {
+            write!(f, " ({})", self.accessible_scopes.join(", "))?;
+        }
+        Ok(())
+    }
+}
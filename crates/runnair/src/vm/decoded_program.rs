@@ -0,0 +1,82 @@
+use crate::memory::relocatable::{MaybeRelocatable, Relocatable, Segment};
+use crate::memory::{MaybeRelocatableAddr, Memory};
+use crate::utils::{u32_from_usize, usize_from_u32};
+use crate::vm::error::InstructionError;
+use crate::vm::opcode::Opcode;
+use crate::vm::{Instruction, InstructionFn};
+
+/// An instruction with its opcode and handler looked up ahead of execution.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct DecodedInstruction {
+    pub opcode: Opcode,
+    pub handler: InstructionFn,
+    pub instruction: Instruction,
+}
+
+impl DecodedInstruction {
+    pub fn decode(instruction: Instruction) -> Result<Self, InstructionError> {
+        let opcode = Opcode::try_from(instruction.op)?;
+        Ok(Self {
+            opcode,
+            handler: opcode.handler(),
+            instruction,
+        })
+    }
+}
+
+/// The instructions of the program segment, decoded once so that each step skips reading and
+/// decoding the instruction at `pc`.
+///
+/// Cells that do not hold a valid instruction are left undecoded, and executing them goes through
+/// memory to report the error. Hints may overwrite the program segment, so the cache is rebuilt
+/// whenever memory records a new overwrite.
+#[derive(Debug)]
+pub(crate) struct DecodedProgram {
+    segment: Segment,
+    instructions: Vec<Option<DecodedInstruction>>,
+    /// The number of overwrites in memory when the program was decoded.
+    n_overwrites: usize,
+}
+
+impl DecodedProgram {
+    pub fn new(memory: &Memory, segment: Segment) -> Self {
+        let instructions = (0..memory.used_size(segment))
+            .map(|offset| {
+                let value = memory.get(Relocatable::from((segment, u32_from_usize(offset))));
+                let Some(MaybeRelocatable::Absolute(instruction)) = value else {
+                    return None;
+                };
+                DecodedInstruction::decode(Instruction::from(instruction)).ok()
+            })
+            .collect();
+
+        Self {
+            segment,
+            instructions,
+            n_overwrites: memory.overwrites().len(),
+        }
+    }
+
+    pub fn segment(&self) -> Segment {
+        self.segment
+    }
+
+    /// Decodes the program again if memory was overwritten since it was decoded.
+    pub fn refresh(&mut self, memory: &Memory) {
+        if memory.overwrites().len() != self.n_overwrites {
+            *self = Self::new(memory, self.segment);
+        }
+    }
+
+    /// The decoded instruction at `pc`, if `pc` is in the program segment and holds a valid
+    /// instruction.
+    pub fn get(&self, pc: MaybeRelocatableAddr) -> Option<DecodedInstruction> {
+        let MaybeRelocatable::Relocatable(Relocatable { segment, offset }) = pc else {
+            return None;
+        };
+        if segment != self.segment {
+            return None;
+        }
+        *self.instructions.get(usize_from_u32(offset.0))?
+    }
+}
@@ -0,0 +1,213 @@
+use std::fmt::{self, Display};
+
+use stwo_prover::core::fields::m31::M31;
+use stwo_prover::core::fields::qm31::QM31;
+use thiserror::Error;
+
+use crate::memory::relocatable::{Relocatable, RelocatableError};
+use crate::memory::{MaybeRelocatableAddr, MaybeRelocatableValue, MemoryError};
+use crate::vm::debug_info::InstructionLocation;
+use crate::vm::hints::HintError;
+use crate::vm::{Instruction, State};
+
+/// An error raised while running a program.
+///
+/// Errors raised by an instruction carry the state before the failing instruction, and the
+/// instruction itself.
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum VmError {
+    #[error("Invalid instruction at pc {:?}: {value:?}.", state.pc)]
+    InvalidInstruction {
+        state: State,
+        value: Option<MaybeRelocatableValue>,
+    },
+    #[error("Unknown opcode {} at pc {:?}.", instruction.op, state.pc)]
+    UnknownOpcode {
+        state: State,
+        instruction: Instruction,
+    },
+    #[error("Unknown memory cell {address:?} at pc {:?}.", state.pc)]
+    UnknownMemoryCell {
+        state: State,
+        instruction: Instruction,
+        address: MaybeRelocatableAddr,
+    },
+    #[error("Assertion failed at pc {:?}: {expected:?} != {actual:?}.", state.pc)]
+    AssertionFailed {
+        state: State,
+        instruction: Instruction,
+        expected: Box<MaybeRelocatableValue>,
+        actual: Box<MaybeRelocatableValue>,
+    },
+    #[error("Failed deduction at pc {:?}: {reason}", state.pc)]
+    DeductionFailed {
+        state: State,
+        instruction: Instruction,
+        reason: &'static str,
+    },
+    #[error("Relocatable arithmetic error at pc {:?}: {error}", state.pc)]
+    Relocatable {
+        state: State,
+        instruction: Instruction,
+        error: RelocatableError,
+    },
+    #[error("Address {value} is not in the base field at pc {:?}.", state.pc)]
+    NonBaseFieldAddress {
+        state: State,
+        instruction: Instruction,
+        value: QM31,
+    },
+    #[error("Memory error at pc {:?}: {error}", state.pc)]
+    Memory {
+        state: State,
+        instruction: Instruction,
+        error: Box<MemoryError>,
+    },
+    #[error("`pc` must be a relocatable value; got {:?}.", state.pc)]
+    NonRelocatablePc { state: State },
+    #[error("Only final `fp` is allowed when at final `pc`; got {:?}.", state.fp)]
+    UnexpectedFinalFp { state: State },
+    #[error("Step limit of {max_steps} exceeded at pc {:?}.", state.pc)]
+    StepLimitExceeded { state: State, max_steps: usize },
+    #[error("Infinite loop at pc {:?}: the state repeats without memory writes.", state.pc)]
+    InfiniteLoop { state: State },
+    #[error("Missing return value at {address:?}.")]
+    MissingReturnValue {
+        state: State,
+        address: MaybeRelocatableAddr,
+    },
+    #[error("Hint `{code}` failed at pc {:?}: {error}", state.pc)]
+    Hint {
+        state: State,
+        code: String,
+        error: Box<HintError>,
+    },
+}
+
+/// A `VmError` with the source location of the failing instruction and the calls leading to it,
+/// when the program has debug info.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VmException {
+    pub error: Box<VmError>,
+    pub location: Option<InstructionLocation>,
+    /// The calls leading to the failing instruction, outermost first.
+    pub traceback: Vec<CallSite>,
+}
+
+/// A call instruction in a traceback.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CallSite {
+    pub pc: Relocatable,
+    pub location: Option<InstructionLocation>,
+}
+
+impl Display for VmException {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(location) = &self.location {
+            write!(f, "{location}: ")?;
+        }
+        write!(f, "{}", self.error)?;
+        if !self.traceback.is_empty() {
+            write!(f, "\nTraceback (most recent call last):")?;
+        }
+        for CallSite { pc, location } in &self.traceback {
+            match location {
+                Some(location) => write!(f, "\n    {location} (pc {pc})")?,
+                None => write!(f, "\n    Unknown location (pc {pc})")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for VmException {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&*self.error)
+    }
+}
+
+/// An error raised while loading a program or preparing it to run.
+#[derive(Debug, Error)]
+pub enum ProgramError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Unknown entry point: {0}.")]
+    UnknownEntryPoint(String),
+    #[error(transparent)]
+    Memory(#[from] MemoryError),
+    #[error("No processor is registered for the hint at pc {pc}: `{code}`.")]
+    UnknownHint { pc: Relocatable, code: String },
+}
+
+/// An error raised by an instruction handler, before it is tagged with the failing state and
+/// instruction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Error)]
+pub enum InstructionError {
+    #[error("Unknown opcode: {0}.")]
+    UnknownOpcode(M31),
+    #[error("Unknown memory cell: {0:?}.")]
+    UnknownMemoryCell(MaybeRelocatableAddr),
+    #[error("Assertion failed: {expected:?} != {actual:?}.")]
+    AssertionFailed {
+        expected: MaybeRelocatableValue,
+        actual: MaybeRelocatableValue,
+    },
+    #[error("{0}")]
+    DeductionFailed(&'static str),
+    #[error(transparent)]
+    Relocatable(RelocatableError),
+    #[error("Address {0} is not in the base field.")]
+    NonBaseFieldAddress(QM31),
+    #[error(transparent)]
+    Memory(#[from] MemoryError),
+}
+
+impl From<RelocatableError> for InstructionError {
+    fn from(error: RelocatableError) -> Self {
+        match error {
+            RelocatableError::NonBaseField(value) => Self::NonBaseFieldAddress(value),
+            error => Self::Relocatable(error),
+        }
+    }
+}
+
+impl InstructionError {
+    pub(crate) fn with_context(self, state: State, instruction: Instruction) -> VmError {
+        match self {
+            Self::UnknownOpcode(_) => VmError::UnknownOpcode { state, instruction },
+            Self::UnknownMemoryCell(address) => VmError::UnknownMemoryCell {
+                state,
+                instruction,
+                address,
+            },
+            Self::AssertionFailed { expected, actual } => VmError::AssertionFailed {
+                state,
+                instruction,
+                expected: Box::new(expected),
+                actual: Box::new(actual),
+            },
+            Self::DeductionFailed(reason) => VmError::DeductionFailed {
+                state,
+                instruction,
+                reason,
+            },
+            Self::Relocatable(error) => VmError::Relocatable {
+                state,
+                instruction,
+                error,
+            },
+            Self::NonBaseFieldAddress(value) => VmError::NonBaseFieldAddress {
+                state,
+                instruction,
+                value,
+            },
+            Self::Memory(error) => VmError::Memory {
+                state,
+                instruction,
+                error: Box::new(error),
+            },
+        }
+    }
+}
@@ -1,54 +1,264 @@
-use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+use stwo_prover::core::fields::m31::M31;
+use thiserror::Error;
 
 use crate::memory::relocatable::{MaybeRelocatable, Relocatable};
-use crate::memory::Memory;
-use crate::utils::{qm31_from_hex_str_array, usize_from_u32};
+use crate::memory::{Memory, MemoryError};
+use crate::utils::u32_from_usize;
+use crate::vm::error::{ProgramError, VmError};
 use crate::vm::{Input, State};
 
-// TODO: add custom (de)serialization.
-#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
-pub enum Hint {
-    #[serde(rename = "memory[state.fp] = input['fibonacci_claim_index'];")]
-    FibonacciIndex,
+/// The hint codes of a program, by pc. The hints at a pc run in order, before its instruction.
+pub type Hints = Vec<Vec<String>>;
+
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum HintError {
+    #[error("Missing input: {0}.")]
+    MissingInput(String),
+    #[error("Invalid input: `{key}` is not {expected}.")]
+    InvalidInput { key: String, expected: &'static str },
+    #[error("Invalid input key: `{0}`.")]
+    InvalidInputKey(String),
+    #[error(transparent)]
+    Memory(Box<MemoryError>),
+    #[error("{0}")]
+    Custom(String),
 }
 
-impl Hint {
-    fn execute(&self, memory: &mut Memory, state: &State, input: &Input) {
-        match self {
-            Self::FibonacciIndex => {
-                let index =
-                    Deserialize::deserialize(input.get("fibonacci_claim_index").unwrap()).unwrap();
-                memory.insert(state.fp, qm31_from_hex_str_array(index));
-            }
-        }
+impl From<MemoryError> for HintError {
+    fn from(error: MemoryError) -> Self {
+        Self::Memory(Box::new(error))
     }
 }
 
-pub(crate) type Hints = Vec<Option<Hint>>;
+/// Executes the hints with a given code.
+pub trait HintProcessor: Debug + Send + Sync {
+    fn execute(&self, memory: &mut Memory, state: &State, input: &Input) -> Result<(), HintError>;
+}
+
+/// `memory[state.fp] = input['fibonacci_claim_index'];`
+#[derive(Debug)]
+pub struct FibonacciIndex;
+
+impl FibonacciIndex {
+    pub const CODE: &'static str = "memory[state.fp] = input['fibonacci_claim_index'];";
+}
+
+impl HintProcessor for FibonacciIndex {
+    fn execute(&self, memory: &mut Memory, state: &State, input: &Input) -> Result<(), HintError> {
+        memory.insert(state.fp, input.qm31("fibonacci_claim_index")?)?;
 
+        Ok(())
+    }
+}
+
+/// Runs the hints of a program, dispatching each hint to the processor registered for its code.
 #[derive(Debug)]
-pub(crate) struct HintRunner {
-    pc_to_hint: Hints,
+pub struct HintRunner {
+    processors: HashMap<String, Box<dyn HintProcessor>>,
+    pc_to_hints: HashMap<Relocatable, Vec<String>>,
     input: Input,
 }
 
 impl HintRunner {
-    pub(crate) fn new(pc_to_hint: Hints, input: Input) -> Self {
-        Self { pc_to_hint, input }
+    /// Creates a hint runner with the built-in hints registered.
+    pub fn new(input: Input) -> Self {
+        let mut hint_runner = Self {
+            processors: HashMap::new(),
+            pc_to_hints: HashMap::new(),
+            input,
+        };
+        hint_runner.register(FibonacciIndex::CODE, FibonacciIndex);
+
+        hint_runner
+    }
+
+    pub fn input(&self) -> &Input {
+        &self.input
+    }
+
+    pub(crate) fn set_input(&mut self, input: Input) {
+        self.input = input;
+    }
+
+    /// The loaded hint codes, by pc.
+    pub(crate) fn hints(&self) -> &HashMap<Relocatable, Vec<String>> {
+        &self.pc_to_hints
+    }
+
+    /// Unloads all the hints, keeping the registered processors.
+    pub(crate) fn clear_hints(&mut self) {
+        self.pc_to_hints.clear();
+    }
+
+    /// Registers `processor` for hints with `code`, replacing any previous processor.
+    pub fn register(&mut self, code: impl Into<String>, processor: impl HintProcessor + 'static) {
+        self.processors.insert(code.into(), Box::new(processor));
+    }
+
+    /// Loads the hints of a program whose first instruction is at `base`, after any hints already
+    /// loaded; fails if any of them has no registered processor.
+    pub fn load(&mut self, base: Relocatable, hints: Hints) -> Result<(), ProgramError> {
+        for (offset, codes) in hints.into_iter().enumerate() {
+            if !codes.is_empty() {
+                self.add_hints(base + M31(u32_from_usize(offset)), codes)?;
+            }
+        }
+
+        Ok(())
     }
 
-    pub(crate) fn maybe_execute_hint(&self, memory: &mut Memory, state: &State) {
-        let MaybeRelocatable::Relocatable(Relocatable {
-            segment: _,
-            offset: pc,
-        }) = state.pc
-        else {
-            panic!("`pc` must be a relocatable value.");
+    /// Adds hints to run at `pc`, in any segment, after the hints already there; fails if any of
+    /// them has no registered processor.
+    pub fn add_hints(&mut self, pc: Relocatable, codes: Vec<String>) -> Result<(), ProgramError> {
+        if let Some(code) = codes
+            .iter()
+            .find(|code| !self.processors.contains_key(*code))
+        {
+            return Err(ProgramError::UnknownHint {
+                pc,
+                code: code.clone(),
+            });
+        }
+        self.pc_to_hints.entry(pc).or_default().extend(codes);
+
+        Ok(())
+    }
+
+    /// Runs the hints at `state.pc`, in order.
+    pub(crate) fn maybe_execute_hints(
+        &self,
+        memory: &mut Memory,
+        state: &State,
+    ) -> Result<(), VmError> {
+        let MaybeRelocatable::Relocatable(pc) = state.pc else {
+            return Err(VmError::NonRelocatablePc { state: *state });
         };
 
-        let pc = usize_from_u32(pc.0);
-        if let Some(Some(hint)) = self.pc_to_hint.get(pc) {
-            hint.execute(memory, state, &self.input);
+        for code in self.pc_to_hints.get(&pc).into_iter().flatten() {
+            self.processors[code]
+                .execute(memory, state, &self.input)
+                .map_err(|error| VmError::Hint {
+                    state: *state,
+                    code: code.clone(),
+                    error: Box::new(error),
+                })?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use stwo_prover::core::fields::m31::M31;
+
+    use super::*;
+    use crate::vm::{Program, VM};
+
+    /// `memory[state.ap] = 7`, or fails if `fail` is set.
+    #[derive(Debug)]
+    struct WriteSeven {
+        fail: bool,
+    }
+
+    impl HintProcessor for WriteSeven {
+        fn execute(&self, memory: &mut Memory, state: &State, _: &Input) -> Result<(), HintError> {
+            if self.fail {
+                return Err(HintError::Custom("Failed.".to_string()));
+            }
+            memory.insert(state.ap, M31(7))?;
+
+            Ok(())
+        }
+    }
+
+    /// A program running the hint `code` before `[ap] = 7; ret`.
+    fn program_with_hint(code: &str) -> Program {
+        Program {
+            hints: vec![vec![code.to_string()]],
+            ..Program::from_instructions(&[[37, 0, 7, 0], [171, 0, 0, 0]])
+        }
+    }
+
+    #[test]
+    fn test_unknown_hint() {
+        let program = program_with_hint("memory[ap] = 7");
+
+        let error = VM::create_for_main_entry_point(program, HintRunner::new(Input::default()))
+            .unwrap_err();
+
+        assert!(
+            matches!(error, ProgramError::UnknownHint { pc, code } if pc == (0, 0).into() && code == "memory[ap] = 7")
+        );
+    }
+
+    #[test]
+    fn test_custom_hint() {
+        let program = program_with_hint("memory[ap] = 7");
+        let mut hint_runner = HintRunner::new(Input::default());
+        hint_runner.register("memory[ap] = 7", WriteSeven { fail: false });
+        let mut vm = VM::create_for_main_entry_point(program, hint_runner).unwrap();
+        let ap = vm.state().ap();
+
+        vm.execute().unwrap();
+
+        assert_eq!(vm.memory().get(ap), Some(M31(7).into()));
+    }
+
+    /// Appends its name to a shared log.
+    #[derive(Debug)]
+    struct Record {
+        name: &'static str,
+        log: Arc<Mutex<Vec<&'static str>>>,
+    }
+
+    impl HintProcessor for Record {
+        fn execute(&self, _: &mut Memory, _: &State, _: &Input) -> Result<(), HintError> {
+            self.log.lock().unwrap().push(self.name);
+
+            Ok(())
         }
     }
+
+    #[test]
+    fn test_multiple_hints() {
+        // `[ap] = 7; ret`, with two hints at pc 0.
+        let program: Program = serde_json::from_value(serde_json::json!({
+            "data": [["0x25", "0x0", "0x7", "0x0"], ["0xab", "0x0", "0x0", "0x0"]],
+            "hints": { "0": [{ "code": "first" }, { "code": "second" }] },
+        }))
+        .unwrap();
+        let log = Arc::new(Mutex::new(vec![]));
+        let mut hint_runner = HintRunner::new(Input::default());
+        for name in ["first", "second", "other"] {
+            let log = log.clone();
+            hint_runner.register(name, Record { name, log });
+        }
+        // At offset 0 of another segment, so it must not run at pc 0.
+        hint_runner
+            .add_hints((5, 0).into(), vec!["other".to_string()])
+            .unwrap();
+        let mut vm = VM::create_for_main_entry_point(program, hint_runner).unwrap();
+
+        vm.execute().unwrap();
+
+        assert_eq!(*log.lock().unwrap(), ["first", "second"]);
+    }
+
+    #[test]
+    fn test_failed_hint() {
+        let program = program_with_hint("memory[ap] = 7");
+        let mut hint_runner = HintRunner::new(Input::default());
+        hint_runner.register("memory[ap] = 7", WriteSeven { fail: true });
+        let mut vm = VM::create_for_main_entry_point(program, hint_runner).unwrap();
+
+        let error = *vm.execute().unwrap_err().error;
+
+        assert!(matches!(error, VmError::Hint { code, .. } if code == "memory[ap] = 7"));
+    }
 }
@@ -0,0 +1,367 @@
+use std::fmt;
+
+use serde::de::{self, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use stwo_prover::core::fields::m31::{M31, P};
+use stwo_prover::core::fields::qm31::QM31;
+
+use crate::memory::relocatable::Relocatable;
+use crate::memory::segments::SegmentKind;
+use crate::memory::{MaybeRelocatableValue, Memory};
+use crate::utils::u32_from_usize;
+use crate::vm::hints::HintError;
+
+/// A value of the program input.
+///
+/// In the input JSON, felts are hex strings, with or without a `0x` prefix, or numbers; arrays are
+/// JSON arrays and structs are JSON objects. A QM31 is an array of its 4 coordinates.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InputValue {
+    Felt(M31),
+    Array(Vec<InputValue>),
+    /// The fields of a struct, in input order.
+    Struct(Vec<(String, InputValue)>),
+}
+
+/// The program input, read by hints: a struct of named values.
+///
+/// Values are looked up by key: a field name, followed by `.field` to read the field of a struct
+/// and `[index]` to read the element of an array, as in `claim.values[2]`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Input(InputValue);
+
+impl Default for Input {
+    fn default() -> Self {
+        Self(InputValue::Struct(vec![]))
+    }
+}
+
+impl Input {
+    /// The value at `key`.
+    pub fn get(&self, key: &str) -> Result<&InputValue, HintError> {
+        let parts = key_parts(key).ok_or_else(|| HintError::InvalidInputKey(key.to_string()))?;
+        let mut value = &self.0;
+        let mut parent_end = 0;
+        for (end, part) in parts {
+            value = match (value, part) {
+                (InputValue::Struct(fields), KeyPart::Field(name)) => fields
+                    .iter()
+                    .find_map(|(field, value)| (field == name).then_some(value)),
+                (InputValue::Array(elements), KeyPart::Index(index)) => elements.get(index),
+                (_, KeyPart::Field(_)) => {
+                    return Err(invalid_input(&key[..parent_end], "a struct"))
+                }
+                (_, KeyPart::Index(_)) => {
+                    return Err(invalid_input(&key[..parent_end], "an array"))
+                }
+            }
+            .ok_or_else(|| HintError::MissingInput(key[..end].to_string()))?;
+            parent_end = end;
+        }
+
+        Ok(value)
+    }
+
+    /// The felt at `key`.
+    pub fn felt(&self, key: &str) -> Result<M31, HintError> {
+        match self.get(key)? {
+            InputValue::Felt(value) => Ok(*value),
+            _ => Err(invalid_input(key, "a felt")),
+        }
+    }
+
+    /// The QM31 at `key`, given as an array of its 4 coordinates.
+    pub fn qm31(&self, key: &str) -> Result<QM31, HintError> {
+        let coordinates = match self.get(key)? {
+            InputValue::Array(elements) => elements
+                .iter()
+                .map(|element| match element {
+                    InputValue::Felt(value) => Some(*value),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>(),
+            _ => None,
+        };
+        let coordinates = coordinates
+            .and_then(|coordinates| <[M31; 4]>::try_from(coordinates).ok())
+            .ok_or_else(|| invalid_input(key, "a QM31"))?;
+
+        Ok(QM31::from_m31_array(coordinates))
+    }
+
+    /// The elements of the array at `key`.
+    pub fn array(&self, key: &str) -> Result<&[InputValue], HintError> {
+        match self.get(key)? {
+            InputValue::Array(elements) => Ok(elements),
+            _ => Err(invalid_input(key, "an array")),
+        }
+    }
+
+    /// The fields of the struct at `key`, in input order.
+    pub fn struct_fields(&self, key: &str) -> Result<&[(String, InputValue)], HintError> {
+        match self.get(key)? {
+            InputValue::Struct(fields) => Ok(fields),
+            _ => Err(invalid_input(key, "a struct")),
+        }
+    }
+
+    /// Writes the value at `key` to a new input segment, and returns a pointer to it.
+    ///
+    /// The segment of an array holds its elements, and other values are written as a single
+    /// element. Felts take a cell and structs take the cells of their fields, in order, while
+    /// nested arrays are written to their own segments and take a cell with a pointer to them.
+    pub fn load(&self, memory: &mut Memory, key: &str) -> Result<Relocatable, HintError> {
+        match self.get(key)? {
+            InputValue::Array(elements) => write_segment(memory, elements),
+            value => write_segment(memory, std::slice::from_ref(value)),
+        }
+    }
+}
+
+fn invalid_input(key: &str, expected: &'static str) -> HintError {
+    HintError::InvalidInput {
+        key: key.to_string(),
+        expected,
+    }
+}
+
+/// Writes `elements` to a new input segment, and returns its base.
+fn write_segment(memory: &mut Memory, elements: &[InputValue]) -> Result<Relocatable, HintError> {
+    let mut cells = vec![];
+    for element in elements {
+        flatten(memory, element, &mut cells)?;
+    }
+    let base = memory.add_segment(SegmentKind::Input);
+    for (offset, cell) in cells.into_iter().enumerate() {
+        memory.insert(base + M31(u32_from_usize(offset)), cell)?;
+    }
+
+    Ok(base)
+}
+
+/// Appends the cells of `value` to `cells`, writing its nested arrays to new segments.
+fn flatten(
+    memory: &mut Memory,
+    value: &InputValue,
+    cells: &mut Vec<MaybeRelocatableValue>,
+) -> Result<(), HintError> {
+    match value {
+        InputValue::Felt(value) => cells.push((*value).into()),
+        InputValue::Array(elements) => cells.push(write_segment(memory, elements)?.into()),
+        InputValue::Struct(fields) => {
+            for (_, value) in fields {
+                flatten(memory, value, cells)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum KeyPart<'a> {
+    Field(&'a str),
+    Index(usize),
+}
+
+/// Splits `key` into its parts, each with the length of the key up to its end, or returns `None`
+/// if `key` is malformed.
+fn key_parts(key: &str) -> Option<Vec<(usize, KeyPart<'_>)>> {
+    let mut parts = vec![];
+    let mut rest = key;
+    loop {
+        let name_end = rest.find(['.', '[']).unwrap_or(rest.len());
+        if name_end == 0 {
+            return None;
+        }
+        let (name, tail) = rest.split_at(name_end);
+        rest = tail;
+        parts.push((key.len() - rest.len(), KeyPart::Field(name)));
+        while let Some(tail) = rest.strip_prefix('[') {
+            let (index, tail) = tail.split_once(']')?;
+            rest = tail;
+            parts.push((key.len() - rest.len(), KeyPart::Index(index.parse().ok()?)));
+        }
+        match rest.strip_prefix('.') {
+            Some(tail) => rest = tail,
+            None if rest.is_empty() => return Some(parts),
+            None => return None,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for InputValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(InputValueVisitor)
+    }
+}
+
+struct InputValueVisitor;
+
+impl<'de> Visitor<'de> for InputValueVisitor {
+    type Value = InputValue;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("a felt, an array or a struct")
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Self::Value, E> {
+        match u32::try_from(value) {
+            Ok(value) if value < P => Ok(InputValue::Felt(M31(value))),
+            _ => Err(E::invalid_value(de::Unexpected::Unsigned(value), &"a felt")),
+        }
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Self::Value, E> {
+        match u64::try_from(value) {
+            Ok(value) => self.visit_u64(value),
+            Err(_) => Err(E::invalid_value(de::Unexpected::Signed(value), &"a felt")),
+        }
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+        match u32::from_str_radix(value.trim_start_matches("0x"), 16) {
+            Ok(felt) if felt < P => Ok(InputValue::Felt(M31(felt))),
+            _ => Err(E::invalid_value(de::Unexpected::Str(value), &"a hex felt")),
+        }
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut elements = vec![];
+        while let Some(element) = seq.next_element()? {
+            elements.push(element);
+        }
+        Ok(InputValue::Array(elements))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut fields = vec![];
+        while let Some(field) = map.next_entry()? {
+            fields.push(field);
+        }
+        Ok(InputValue::Struct(fields))
+    }
+}
+
+/// Serializes to the input JSON format, with felts as numbers.
+impl Serialize for InputValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Felt(value) => serializer.serialize_u32(value.0),
+            Self::Array(elements) => serializer.collect_seq(elements),
+            Self::Struct(fields) => serializer.collect_map(fields.iter().map(|(k, v)| (k, v))),
+        }
+    }
+}
+
+impl Serialize for Input {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Input {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match InputValue::deserialize(deserializer)? {
+            value @ InputValue::Struct(_) => Ok(Self(value)),
+            _ => Err(de::Error::custom("the input must be a struct")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use stwo_prover::core::fields::m31::M31;
+
+    use super::*;
+    use crate::memory::relocatable::MaybeRelocatable;
+
+    fn input() -> Input {
+        serde_json::from_str(
+            r#"{
+                "index": "0x5",
+                "claim": { "values": [1, "0x2", [3, 4]], "point": ["0x1", "0x2", "0x3", "0x4"] }
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_typed_values() {
+        let input = input();
+
+        assert_eq!(input.felt("index"), Ok(M31(5)));
+        assert_eq!(input.felt("claim.values[1]"), Ok(M31(2)));
+        assert_eq!(input.felt("claim.values[2][1]"), Ok(M31(4)));
+        assert_eq!(
+            input.qm31("claim.point"),
+            Ok(QM31::from_m31_array([1, 2, 3, 4].map(M31)))
+        );
+        assert_eq!(input.array("claim.values").unwrap().len(), 3);
+        let fields = input.struct_fields("claim").unwrap();
+        assert_eq!(fields[0].0, "values");
+        assert_eq!(fields[1].0, "point");
+    }
+
+    #[test]
+    fn test_input_errors() {
+        let input = input();
+
+        assert_eq!(
+            input.felt("claim.value"),
+            Err(HintError::MissingInput("claim.value".to_string()))
+        );
+        assert_eq!(
+            input.felt("claim.values[3]"),
+            Err(HintError::MissingInput("claim.values[3]".to_string()))
+        );
+        assert_eq!(
+            input.felt("claim.values"),
+            Err(invalid_input("claim.values", "a felt"))
+        );
+        assert_eq!(
+            input.qm31("claim.values"),
+            Err(invalid_input("claim.values", "a QM31"))
+        );
+        assert_eq!(
+            input.felt("index.value"),
+            Err(invalid_input("index", "a struct"))
+        );
+        assert_eq!(
+            input.felt("claim[0]"),
+            Err(invalid_input("claim", "an array"))
+        );
+        assert_eq!(
+            input.felt("claim..values"),
+            Err(HintError::InvalidInputKey("claim..values".to_string()))
+        );
+
+        assert!(serde_json::from_str::<Input>(r#"{ "index": "0x80000000" }"#).is_err());
+        assert!(serde_json::from_str::<Input>(r#"{ "index": -1 }"#).is_err());
+        assert!(serde_json::from_str::<Input>(r#"["0x1"]"#).is_err());
+    }
+
+    #[test]
+    fn test_load() {
+        let input = input();
+        let mut memory = Memory::default();
+
+        let claim = input.load(&mut memory, "claim").unwrap();
+        let pointer = |addr| match memory.get(addr) {
+            Some(MaybeRelocatable::Relocatable(pointer)) => pointer,
+            value => panic!("Expected a pointer, got {value:?}."),
+        };
+        let values = pointer(claim);
+        let point = pointer(claim + M31(1));
+
+        assert_eq!(memory.used_size(claim.segment), 2);
+        assert_eq!(memory.get(values + M31(1)), Some(M31(2).into()));
+        assert_eq!(memory.get(pointer(values + M31(2))), Some(M31(3).into()));
+        let point = (0..4).map(|offset| memory.get(point + M31(offset)));
+        assert!(point.eq([1, 2, 3, 4].map(|x| Some(M31(x).into()))));
+        assert_eq!(memory.segment_kinds(), [SegmentKind::Input; 4]);
+
+        let index = input.load(&mut memory, "index").unwrap();
+        assert_eq!(memory.get(index), Some(M31(5).into()));
+    }
+}
@@ -1,140 +1,43 @@
 use num_traits::Zero;
-use paste::paste;
-use stwo_prover::core::fields::m31::M31;
 use stwo_prover::core::fields::qm31::QM31;
 
-use crate::memory::relocatable::{assert_and_project, MaybeRelocatable};
+use crate::memory::relocatable::{MaybeRelocatable, RelocatableError};
 use crate::memory::{MaybeRelocatableAddr, Memory};
-use crate::vm::jmp::{jmp_rel, jmp_rel_appp};
-use crate::vm::{resolve_addresses, InstructionArgs, State};
-
-fn resolve_jnz_args(
+use crate::vm::error::InstructionError;
+use crate::vm::jmp::jmp_rel;
+use crate::vm::opcode::{Operand, Register};
+use crate::vm::{
+    operand, read_memory, resolve_addresses, InstructionArgs, InstructionResult, State,
+};
+
+fn resolve_condition(
     memory: &Memory,
-    state: State,
-    bases: &[&str; 2],
-    offsets: &[M31; 2],
-) -> (MaybeRelocatableAddr, QM31) {
-    let [dest_addr, cond_addr] = resolve_addresses(state, bases, offsets);
-    let Some(destination) = memory.get(dest_addr) else {
-        panic!("Destination cannot be deduced.")
-    };
-    let Some(MaybeRelocatable::Absolute(condition)) = memory.get(cond_addr) else {
-        panic!("Condition must be an absolute value.")
-    };
-
-    (assert_and_project(destination), condition)
+    cond_addr: MaybeRelocatableAddr,
+) -> Result<QM31, InstructionError> {
+    match read_memory(memory, cond_addr)? {
+        MaybeRelocatable::Absolute(condition) => Ok(condition),
+        MaybeRelocatable::Relocatable(condition) => {
+            Err(RelocatableError::ExpectedAbsolute(condition).into())
+        }
+    }
 }
 
-fn resolve_jnz_imm_args(
+/// `jmp rel target if [cond+args[1]] != 0`.
+pub(crate) fn jnz(
     memory: &Memory,
     state: State,
-    base: &str,
-    offsets: &[M31; 2],
-) -> (M31, QM31) {
-    let [cond_addr] = resolve_addresses(state, &[base], &[offsets[1]]);
-    let destination = offsets[0];
-    let Some(MaybeRelocatable::Absolute(condition)) = memory.get(cond_addr) else {
-        panic!("Condition must be an absolute value.")
-    };
-
-    (destination, condition)
-}
-
-fn jnz(state: State, destination: impl Into<MaybeRelocatableAddr>, condition: impl Zero) -> State {
-    if condition.is_zero() {
-        state.advance()
-    } else {
-        jmp_rel(state, destination.into())
-    }
-}
+    target: Operand,
+    cond: Register,
+    args: &InstructionArgs,
+) -> InstructionResult {
+    let destination = operand::read(memory, state, target, args)?;
+    let [cond_addr] = resolve_addresses(state, &[cond], &[args[1]]);
+    let condition = resolve_condition(memory, cond_addr)?;
+    let destination = MaybeRelocatableAddr::try_from(destination)?;
 
-fn jnz_appp(
-    state: State,
-    destination: impl Into<MaybeRelocatableAddr>,
-    condition: impl Zero,
-) -> State {
     if condition.is_zero() {
-        state.advance_and_increment_ap()
+        Ok(state.advance())
     } else {
-        jmp_rel_appp(state, destination.into())
+        jmp_rel(state, destination)
     }
 }
-
-macro_rules! define_jnz {
-    ($cond:ident, $dest:ident) => {
-        paste! {
-            /// Jump-not-zero without incrementing `ap`: `jnz_[ap/fp]_[ap/fp]`.
-            pub(crate) fn [<jnz_ $cond _ $dest >] (
-                memory: &mut Memory,
-                state: State,
-                args: InstructionArgs,
-            ) -> State {
-                let (destination, condition) = resolve_jnz_args(
-                    memory,
-                    state,
-                    &[stringify!($cond), stringify!($dest)],
-                    &[args[0], args[1]],
-                );
-                jnz(state, destination, condition)
-            }
-
-            /// Jump-not-zero with incrementing `ap`: `jnz_[ap/fp]_[ap/fp][_appp]`.
-            pub(crate) fn [<jnz_ $cond _ $dest _appp>] (
-                memory: &mut Memory,
-                state: State,
-                args: InstructionArgs,
-            ) -> State {
-                let (destination, condition) = resolve_jnz_args(
-                    memory,
-                    state,
-                    &[stringify!($cond), stringify!($dest)],
-                    &[args[0], args[1]],
-                );
-                jnz_appp(state, destination, condition)
-            }
-        }
-    };
-}
-
-macro_rules! define_jnz_imm {
-    ($dest:ident) => {
-        paste! {
-            /// Jump-not-zero without incrementing `ap`: `jnz_imm_[ap/fp]`.
-            pub(crate) fn [<jnz_imm_ $dest >] (
-                memory: &mut Memory,
-                state: State,
-                args: InstructionArgs,
-            ) -> State {
-                let (destination, condition) = resolve_jnz_imm_args(
-                    memory,
-                    state,
-                    stringify!($dest),
-                    &[args[0], args[1]],
-                );
-                jnz(state, destination, condition)
-            }
-
-            /// Jump-not-zero with incrementing `ap`: `jnz_imm_[ap/fp]_appp`.
-            pub(crate) fn [<jnz_imm_ $dest _appp>] (
-                memory: &mut Memory,
-                state: State,
-                args: InstructionArgs,
-            ) -> State {
-                let (destination, condition) = resolve_jnz_imm_args(
-                    memory,
-                    state,
-                    stringify!($dest),
-                    &[args[0], args[1]],
-                );
-                jnz_appp(state, destination, condition)
-            }
-        }
-    };
-}
-
-define_jnz!(ap, ap);
-define_jnz!(ap, fp);
-define_jnz!(fp, ap);
-define_jnz!(fp, fp);
-define_jnz_imm!(ap);
-define_jnz_imm!(fp);
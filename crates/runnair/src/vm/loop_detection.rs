@@ -0,0 +1,64 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::memory::Memory;
+use crate::vm::State;
+
+/// The number of states recorded before starting over, which bounds the memory of the detector.
+const MAX_STATES: usize = 1 << 16;
+
+/// Detects runs that return to an earlier state without changing memory in between. Such runs
+/// repeat the same instructions on the same memory, so they never end.
+///
+/// Only loops of fewer than `MAX_STATES` steps are detected; longer ones are left to the step
+/// limit.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub(crate) struct LoopDetector {
+    n_writes: usize,
+    states: HashSet<State>,
+}
+
+impl LoopDetector {
+    /// Records the state about to be executed, and returns whether the run is stuck.
+    pub(crate) fn is_stuck(&mut self, state: State, memory: &Memory) -> bool {
+        if memory.n_writes() != self.n_writes || self.states.len() == MAX_STATES {
+            self.n_writes = memory.n_writes();
+            self.states.clear();
+        }
+
+        !self.states.insert(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::relocatable::Relocatable;
+
+    fn state(ap: u32, pc: u32) -> State {
+        State {
+            ap: Relocatable::from((1, ap)).into(),
+            fp: Relocatable::from((1, 0)).into(),
+            pc: Relocatable::from((0, pc)).into(),
+        }
+    }
+
+    #[test]
+    fn test_bounded_states() {
+        let memory = Memory::default();
+        let mut loop_detector = LoopDetector::default();
+
+        // `ap += 1; jmp rel -1`, which never returns to a state.
+        for ap in 0..2 * MAX_STATES as u32 {
+            assert!(!loop_detector.is_stuck(state(ap, ap % 2), &memory));
+            assert!(loop_detector.states.len() <= MAX_STATES);
+        }
+
+        // `jmp rel 1; jmp rel -1`, after the states were reset.
+        let ap = 2 * MAX_STATES as u32;
+        assert!(!loop_detector.is_stuck(state(ap, 0), &memory));
+        assert!(!loop_detector.is_stuck(state(ap, 1), &memory));
+        assert!(loop_detector.is_stuck(state(ap, 0), &memory));
+    }
+}
@@ -1,36 +1,52 @@
-pub mod add_ap;
-pub mod assert;
-pub mod call;
-pub mod deref;
+mod add_ap;
+mod assert;
+pub mod builtins;
+mod call;
+pub mod debug_info;
+mod decoded_program;
+mod deref;
+pub mod error;
 pub mod hints;
-pub mod jmp;
-pub mod jnz;
-pub mod operand;
+pub mod input;
+mod jmp;
+mod jnz;
+mod loop_detection;
+pub mod opcode;
+mod operand;
+pub mod profiler;
+pub mod resources;
+pub mod snapshot;
+#[cfg(test)]
+mod spec;
+pub mod trace;
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::BufReader;
 use std::path::PathBuf;
 
 use num_traits::Zero;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json;
 use stwo_prover::core::fields::m31::M31;
 use stwo_prover::core::fields::qm31::QM31;
 
-use self::add_ap::*;
-use self::assert::*;
-use self::call::*;
-use self::deref::*;
+use self::builtins::{read_output, Builtin, OutputError};
+use self::debug_info::InstructionLocation;
+use self::decoded_program::{DecodedInstruction, DecodedProgram};
+use self::error::{CallSite, InstructionError, ProgramError, VmError, VmException};
 use self::hints::*;
-use self::jmp::*;
-use self::jnz::*;
-use crate::memory::relocatable::{MaybeRelocatable, Relocatable, Segment};
-use crate::memory::{MaybeRelocatableAddr, Memory};
-use crate::utils::{get_tests_data_dir, m31_from_hex_str, maybe_resize, u32_from_usize};
-
-// TODO: reconsider input type and parsing.
-pub(crate) type Input = serde_json::Value;
+pub use self::input::Input;
+use self::loop_detection::LoopDetector;
+use self::opcode::{ApUpdate, Opcode, Operation, Register};
+use self::profiler::{Profile, Profiler};
+use self::resources::{ExecutionResources, SegmentResources};
+use self::trace::TraceEntry;
+use crate::memory::relocatable::{MaybeRelocatable, Relocatable, RelocationTable};
+use crate::memory::segments::SegmentKind;
+use crate::memory::{MaybeRelocatableAddr, MaybeRelocatableValue, Memory};
+use crate::utils::{m31_from_hex_str, u32_from_usize, usize_from_u32};
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub struct State {
     ap: MaybeRelocatableAddr,
     fp: MaybeRelocatableAddr,
@@ -38,6 +54,25 @@ pub struct State {
 }
 
 impl State {
+    pub fn ap(&self) -> MaybeRelocatableAddr {
+        self.ap
+    }
+
+    pub fn fp(&self) -> MaybeRelocatableAddr {
+        self.fp
+    }
+
+    pub fn pc(&self) -> MaybeRelocatableAddr {
+        self.pc
+    }
+
+    pub fn register(&self, register: Register) -> MaybeRelocatableAddr {
+        match register {
+            Register::Ap => self.ap,
+            Register::Fp => self.fp,
+        }
+    }
+
     pub fn advance(self) -> Self {
         Self {
             ap: self.ap,
@@ -57,7 +92,7 @@ impl State {
 
 pub(crate) type InstructionArgs = [M31; 3];
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Instruction {
     pub op: M31,
     pub args: InstructionArgs,
@@ -80,62 +115,261 @@ impl<T: Into<M31>> From<[T; 4]> for Instruction {
     }
 }
 
-// TODO: add hints.
-#[derive(Debug, Deserialize)]
-#[serde(try_from = "ProgramRaw")]
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(try_from = "ProgramRaw", into = "ProgramRaw")]
 pub struct Program {
     pub instructions: Vec<Instruction>,
     pub hints: Hints,
+    pub builtins: Vec<Builtin>,
+    /// The pc of each function and label, by full name (e.g. `__main__.main`).
+    pub identifiers: HashMap<String, usize>,
+    /// The number of cells each function returns, by full name: its implicit arguments, then its
+    /// return values. Only for the functions whose return type the program defines.
+    pub return_sizes: HashMap<String, usize>,
+    /// The source location of each instruction, by pc, if the program has debug info.
+    pub instruction_locations: HashMap<usize, InstructionLocation>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 struct ProgramRaw {
     data: Vec<[String; 4]>,
-    hints: serde_json::Map<String, serde_json::Value>,
+    hints: HashMap<String, Vec<HintRaw>>,
+    #[serde(default)]
+    builtins: Vec<Builtin>,
+    #[serde(default)]
+    identifiers: HashMap<String, IdentifierRaw>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    debug_info: Option<DebugInfoRaw>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct HintRaw {
+    code: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct IdentifierRaw {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pc: Option<usize>,
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    kind: Option<String>,
+    /// The type of a `type_definition`, e.g. the `Return` type of a function.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cairo_type: Option<String>,
+    /// The number of cells of a `struct`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    size: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct DebugInfoRaw {
+    /// The source location of each instruction, by decimal pc.
+    instruction_locations: HashMap<String, InstructionLocation>,
 }
 
 impl TryFrom<ProgramRaw> for Program {
     type Error = serde_json::Error;
 
     fn try_from(raw_program: ProgramRaw) -> Result<Self, Self::Error> {
-        let instructions: Vec<_> = raw_program
+        let instructions = raw_program
             .data
             .into_iter()
             .map(|instruction| {
-                let raw_instruction = instruction.map(|x| m31_from_hex_str(&x));
-                Instruction::from(raw_instruction)
+                let mut raw_instruction = [M31(0); 4];
+                for (raw, x) in raw_instruction.iter_mut().zip(&instruction) {
+                    *raw = m31_from_hex_str(x).ok_or_else(|| {
+                        serde::de::Error::custom(format!("Invalid instruction word: {x}."))
+                    })?;
+                }
+                Ok(Instruction::from(raw_instruction))
+            })
+            .collect::<Result<_, Self::Error>>()?;
+
+        let mut hints = Hints::new();
+        for (pc, hints_at_pc) in raw_program.hints {
+            let pc = usize::from_str_radix(&pc, 16)
+                .map_err(|_| serde::de::Error::custom(format!("Invalid hint pc: {pc}.")))?;
+            if hints_at_pc.is_empty() {
+                continue;
+            }
+            if hints.len() <= pc {
+                hints.resize(pc + 1, vec![]);
+            }
+            hints[pc] = hints_at_pc.into_iter().map(|hint| hint.code).collect();
+        }
+
+        let return_sizes = raw_program
+            .identifiers
+            .iter()
+            .filter_map(|(name, identifier)| {
+                let function = name.strip_suffix(".Return")?;
+                let return_type = identifier.cairo_type.as_deref()?;
+                let implicit_args_size = raw_program
+                    .identifiers
+                    .get(&format!("{function}.ImplicitArgs"))
+                    .and_then(|implicit_args| implicit_args.size)
+                    .unwrap_or(0);
+                let size =
+                    implicit_args_size + cairo_type_size(return_type, &raw_program.identifiers)?;
+                Some((function.to_string(), size))
             })
             .collect();
 
-        let pc_to_hint = raw_program
-            .hints
+        let identifiers = raw_program
+            .identifiers
             .into_iter()
-            .filter_map(|(pc, hints_at_pc)| {
-                let pc = usize::from_str_radix(&pc, 16).unwrap();
-                let code = hints_at_pc.as_array()?.first()?.get("code")?;
-                // TODO: reconsider this clone.
-                Some((pc, serde_json::from_value(code.clone()).ok()?))
-            });
+            .filter_map(|(name, identifier)| Some((name, identifier.pc?)))
+            .collect();
 
-        let mut hints = Hints::new();
-        for (pc, hint) in pc_to_hint.into_iter() {
-            maybe_resize(&mut hints, pc, None);
-            hints[pc] = Some(hint);
-        }
+        let instruction_locations = raw_program
+            .debug_info
+            .map_or_else(HashMap::new, |debug_info| debug_info.instruction_locations)
+            .into_iter()
+            .map(|(pc, location)| {
+                let pc = pc.parse().map_err(|_| {
+                    serde::de::Error::custom(format!("Invalid instruction location pc: {pc}."))
+                })?;
+                Ok((pc, location))
+            })
+            .collect::<Result<_, Self::Error>>()?;
 
         Ok(Self {
             instructions,
             hints,
+            builtins: raw_program.builtins,
+            identifiers,
+            return_sizes,
+            instruction_locations,
         })
     }
 }
 
+/// The number of cells of a value of `cairo_type`, e.g. `felt`, `felt*` or `(res: felt, x: T)`,
+/// where the size of a struct `T` is read from `identifiers`. `None` for unknown types.
+fn cairo_type_size(
+    cairo_type: &str,
+    identifiers: &HashMap<String, IdentifierRaw>,
+) -> Option<usize> {
+    let cairo_type = cairo_type.trim();
+    if matches!(cairo_type, "felt" | "codeoffset") || cairo_type.ends_with('*') {
+        return Some(1);
+    }
+    let Some(members) = cairo_type
+        .strip_prefix('(')
+        .and_then(|members| members.strip_suffix(')'))
+    else {
+        return identifiers.get(cairo_type)?.size;
+    };
+
+    // Split the members on the commas outside nested tuples.
+    let mut depth = 0;
+    let mut start = 0;
+    let mut member_types = vec![];
+    for (index, char) in members.char_indices().chain([(members.len(), ',')]) {
+        match char {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                let member = members[start..index].trim();
+                // Named members are `name: type`; the name holds no parentheses.
+                let member_type = match member.split_once(':') {
+                    Some((name, member_type)) if !name.contains('(') => member_type,
+                    _ => member,
+                };
+                if !member.is_empty() {
+                    member_types.push(member_type);
+                }
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+
+    member_types
+        .into_iter()
+        .map(|member_type| cairo_type_size(member_type, identifiers))
+        .sum()
+}
+
+impl From<Program> for ProgramRaw {
+    fn from(program: Program) -> Self {
+        let data = program
+            .instructions
+            .into_iter()
+            .map(|Instruction { op, args }| {
+                [op, args[0], args[1], args[2]].map(|x| format!("{:#x}", x.0))
+            })
+            .collect();
+
+        let hints = program
+            .hints
+            .into_iter()
+            .enumerate()
+            .filter(|(_, codes)| !codes.is_empty())
+            .map(|(pc, codes)| {
+                let hints_at_pc = codes.into_iter().map(|code| HintRaw { code }).collect();
+                (format!("{pc:x}"), hints_at_pc)
+            })
+            .collect();
+
+        let functions = program.identifiers.into_iter().map(|(name, pc)| {
+            let identifier = IdentifierRaw {
+                pc: Some(pc),
+                kind: None,
+                cairo_type: None,
+                size: None,
+            };
+            (name, identifier)
+        });
+        // Written as a tuple of felts, with the implicit arguments folded into it.
+        let return_types = program.return_sizes.into_iter().map(|(name, size)| {
+            let identifier = IdentifierRaw {
+                pc: None,
+                kind: Some("type_definition".to_string()),
+                cairo_type: Some(format!("({})", vec!["felt"; size].join(", "))),
+                size: None,
+            };
+            (format!("{name}.Return"), identifier)
+        });
+        let identifiers = functions.chain(return_types).collect();
+
+        let debug_info = (!program.instruction_locations.is_empty()).then(|| DebugInfoRaw {
+            instruction_locations: program
+                .instruction_locations
+                .into_iter()
+                .map(|(pc, location)| (pc.to_string(), location))
+                .collect(),
+        });
+
+        Self {
+            data,
+            hints,
+            builtins: program.builtins,
+            identifiers,
+            debug_info,
+        }
+    }
+}
+
 impl Program {
-    fn from_compiled_file(path: PathBuf) -> Self {
-        let file = File::open(path).unwrap();
+    pub fn from_compiled_file(path: PathBuf) -> Result<Self, ProgramError> {
+        let file = File::open(path)?;
         let reader = BufReader::new(file);
-        let raw_program: ProgramRaw = serde_json::from_reader(reader).unwrap();
-        Program::try_from(raw_program).unwrap()
+        let raw_program: ProgramRaw = serde_json::from_reader(reader)?;
+        Ok(Program::try_from(raw_program)?)
+    }
+
+    /// A program with the given instructions, and no hints, builtins or debug info.
+    #[cfg(test)]
+    pub(crate) fn from_instructions(instructions: &[[u32; 4]]) -> Self {
+        Self {
+            instructions: instructions.iter().map(|&x| Instruction::from(x)).collect(),
+            hints: Hints::new(),
+            builtins: vec![],
+            identifiers: HashMap::new(),
+            return_sizes: HashMap::new(),
+            instruction_locations: HashMap::new(),
+        }
     }
 }
 
@@ -144,6 +378,31 @@ pub struct VM {
     memory: Memory,
     state: State,
     hint_runner: HintRunner,
+    /// The state before each executed instruction, if trace recording is enabled.
+    trace: Option<Vec<State>>,
+    relocated_trace: Option<Vec<TraceEntry>>,
+    relocation_table: Option<RelocationTable>,
+    n_steps: usize,
+    max_steps: Option<usize>,
+    loop_detector: Option<LoopDetector>,
+    /// The base of each builtin segment, in the order of the entry point arguments.
+    builtin_bases: Vec<(Builtin, Relocatable)>,
+    /// The number of cells the entry point returns, starting with the final builtin pointers.
+    return_size: usize,
+    /// The `fp` and `pc` the entry point returns to, which end the run.
+    final_fp: Relocatable,
+    final_pc: Relocatable,
+    /// Whether the run goes through the proof-mode prologue and is padded to a power of two steps.
+    proof_mode: bool,
+    instruction_locations: HashMap<usize, InstructionLocation>,
+    /// The name of the function or label at each pc, from the program identifiers.
+    function_names: HashMap<usize, String>,
+    profiler: Option<Profiler>,
+    decoded_program: DecodedProgram,
+    /// The number of executed instructions of each opcode, by opcode number.
+    opcode_counts: Vec<usize>,
+    /// The highest offset of `ap` in the execution segment so far.
+    max_ap: u32,
 }
 
 impl VM {
@@ -154,326 +413,977 @@ impl VM {
     pub fn state(&self) -> &State {
         &self.state
     }
+
+    /// The number of instructions executed so far.
+    pub fn n_steps(&self) -> usize {
+        self.n_steps
+    }
+
+    pub fn trace(&self) -> Option<&[State]> {
+        self.trace.as_deref()
+    }
+
+    /// The recorded trace with absolute addresses; available once the VM is relocated.
+    pub fn relocated_trace(&self) -> Option<&[TraceEntry]> {
+        self.relocated_trace.as_deref()
+    }
+
+    /// The table the VM was relocated with, if it was relocated.
+    pub fn relocation_table(&self) -> Option<&RelocationTable> {
+        self.relocation_table.as_ref()
+    }
+
+    /// Records the state before each executed instruction from now on.
+    pub fn enable_trace_recording(&mut self) {
+        self.trace.get_or_insert_with(Vec::new);
+    }
+
+    /// The values written to the output builtin by a finished run, up to the final output pointer
+    /// returned by the entry point. Must be called before relocation.
+    pub fn output(&self) -> Result<Vec<QM31>, OutputError> {
+        let (index, &(_, base)) = self
+            .builtin_bases
+            .iter()
+            .enumerate()
+            .find(|(_, (builtin, _))| *builtin == Builtin::Output)
+            .ok_or(OutputError::NoOutputBuiltin)?;
+        // The entry point returns the final builtin pointers, in order, then its return values,
+        // at `[ap - return_size..ap)`.
+        let end = self
+            .memory
+            .get(self.state.ap - M31(u32_from_usize(self.return_size - index)));
+
+        read_output(&self.memory, base, end)
+    }
+
+    /// The last `n_values` values pushed by a finished run, at `[ap - n_values..ap)`, which are
+    /// the return values of the entry point. Must be called before relocation.
+    pub fn return_values(&self, n_values: usize) -> Result<Vec<MaybeRelocatableValue>, VmError> {
+        let n_values = u32_from_usize(n_values);
+        (0..n_values)
+            .map(|index| {
+                let address = self.state.ap - M31(n_values - index);
+                self.memory.get(address).ok_or(VmError::MissingReturnValue {
+                    state: self.state,
+                    address,
+                })
+            })
+            .collect()
+    }
+
+    /// The cells of the execution segment that are part of the public memory of a finished
+    /// proof-mode run: the initial stack of the prologue, with the builtin pointers, and the final
+    /// builtin pointers returned by the entry point. Empty outside proof mode.
+    pub fn public_execution_cells(&self) -> Vec<Relocatable> {
+        let (true, MaybeRelocatable::Relocatable(ap)) = (self.proof_mode, self.state.ap) else {
+            return vec![];
+        };
+        let n_builtins = u32_from_usize(self.builtin_bases.len());
+        let return_size = u32_from_usize(self.return_size);
+        let initial_stack = (0..n_builtins + 2).map(|offset| Relocatable {
+            segment: ap.segment,
+            offset: M31(offset),
+        });
+        let final_pointers = (0..n_builtins).map(|index| ap - M31(return_size - index));
+
+        initial_stack.chain(final_pointers).collect()
+    }
+
+    /// The resources used by the run so far. Must be called before relocation.
+    pub fn execution_resources(&self) -> ExecutionResources {
+        let opcode_counts = Opcode::ALL
+            .into_iter()
+            .filter_map(|opcode| {
+                let count = self.opcode_counts[opcode as usize];
+                (count > 0).then_some((opcode.name(), count))
+            })
+            .collect();
+
+        let segments = self
+            .memory
+            .segment_kinds()
+            .iter()
+            .enumerate()
+            .map(|(segment, &kind)| {
+                let size = self.memory.segment_size(segment);
+                let n_cells = self.memory.n_written(segment);
+                SegmentResources {
+                    kind,
+                    size,
+                    n_cells,
+                    n_holes: size.saturating_sub(n_cells),
+                }
+            })
+            .collect();
+
+        let builtins = self
+            .builtin_bases
+            .iter()
+            .map(|&(builtin, base)| (builtin.name(), self.memory.used_size(base.segment)))
+            .collect();
+
+        ExecutionResources {
+            n_steps: self.n_steps,
+            opcode_counts,
+            segments,
+            max_ap: usize_from_u32(self.max_ap),
+            builtins,
+        }
+    }
+
+    /// Makes `execute` fail once `max_steps` instructions were executed without reaching the end.
+    /// In proof mode, the padding to a power of two counts too.
+    pub fn set_max_steps(&mut self, max_steps: usize) {
+        self.max_steps = Some(max_steps);
+    }
+
+    /// Makes `execute` fail when the VM returns to an earlier state without writing to memory in
+    /// between, which means it is stuck in an infinite loop (e.g. `jmp rel 0`).
+    pub fn enable_loop_detection(&mut self) {
+        self.loop_detector.get_or_insert_with(LoopDetector::default);
+    }
+
+    /// Counts the steps executed in each function from now on, following calls and returns.
+    pub fn enable_profiling(&mut self) {
+        let pc = self.state.pc;
+        self.profiler.get_or_insert_with(|| Profiler::new(pc));
+    }
+
+    /// The steps executed in each function since profiling was enabled, if it was. Functions are
+    /// named after the identifiers at the pcs they were called at.
+    pub fn profile(&self) -> Option<Profile> {
+        let profiler = self.profiler.as_ref()?;
+        Some(profiler.profile(|pc| {
+            self.program_offset(pc)
+                .and_then(|offset| self.function_names.get(&offset).cloned())
+                .unwrap_or_else(|| format!("pc {pc}"))
+        }))
+    }
+
+    /// Relocates the memory and the recorded trace, if any, laying the segments out contiguously.
+    pub fn relocate(&mut self) {
+        let table = self.memory.relocation_table();
+        self.memory.relocate(&table);
+        self.relocated_trace = self
+            .trace
+            .as_ref()
+            .map(|trace| trace.iter().map(|state| state.relocate(&table)).collect());
+        self.relocation_table = Some(table);
+    }
 }
 
 impl VM {
-    const FINAL_FP: (Segment, u32) = (3, 0);
-    const FINAL_PC: (Segment, u32) = (4, 0);
+    /// Creates a VM that runs `program` from pc 0, with the hints run by `hint_runner`.
+    pub fn create_for_main_entry_point(
+        program: Program,
+        hint_runner: HintRunner,
+    ) -> Result<Self, ProgramError> {
+        Self::create_for_pc(program, 0, &[], hint_runner, false)
+    }
+
+    /// Like `create_for_main_entry_point`, but calls the function named `entry_point` (e.g.
+    /// `__main__.fib`) with `args`. The builtin pointers are passed before `args`, as the implicit
+    /// arguments of the function. After execution, the return values of the function can be read
+    /// with `return_values`.
+    pub fn create_for_entry_point(
+        program: Program,
+        entry_point: &str,
+        args: &[MaybeRelocatableValue],
+        hint_runner: HintRunner,
+    ) -> Result<Self, ProgramError> {
+        let pc = *program
+            .identifiers
+            .get(entry_point)
+            .ok_or_else(|| ProgramError::UnknownEntryPoint(entry_point.to_string()))?;
+
+        Self::create_for_pc(program, pc, args, hint_runner, false)
+    }
+
+    /// Creates a VM that runs `program` in proof mode, the way runs are proven: from a prologue
+    /// appended to the program that passes the builtin pointers to the function named
+    /// `entry_point` (e.g. `__main__.main`), calls it and ends in a `jmp rel 0` loop. The run
+    /// stops once it reaches the loop and has executed a power of two steps, so that the trace
+    /// needs no further padding.
+    pub fn create_for_proof_mode(
+        program: Program,
+        entry_point: &str,
+        hint_runner: HintRunner,
+    ) -> Result<Self, ProgramError> {
+        let pc = *program
+            .identifiers
+            .get(entry_point)
+            .ok_or_else(|| ProgramError::UnknownEntryPoint(entry_point.to_string()))?;
 
-    pub fn create_for_main_entry_point(program: Program, input: Input) -> Self {
-        let program_segment = 0;
-        let execution_segment = 1;
-        let output_segment = 2;
+        Self::create_for_pc(program, pc, &[], hint_runner, true)
+    }
+
+    fn create_for_pc(
+        program: Program,
+        pc: usize,
+        args: &[MaybeRelocatableValue],
+        mut hint_runner: HintRunner,
+        proof_mode: bool,
+    ) -> Result<Self, ProgramError> {
+        let entry_pc = pc;
+        let mut memory = Memory::default();
+        let program_base = memory.add_segment(SegmentKind::Program);
+        let execution_base = memory.add_segment(SegmentKind::Execution);
+        let builtin_bases: Vec<_> = program
+            .builtins
+            .iter()
+            .map(|&builtin| (builtin, memory.add_segment(builtin.segment_kind())))
+            .collect();
+        let builtin_pointers = builtin_bases.iter().map(|&(_, base)| base.into());
+
+        let mut instructions = program.instructions;
+        let (frame, initial_fp, final_fp, final_pc, pc): (Vec<MaybeRelocatableValue>, _, _, _, _) =
+            if proof_mode {
+                // The prologue starts with `fp` right after the return `fp` and `pc` of a fake
+                // caller, with the builtin pointers at `[fp]`, and returns to the
+                // `jmp rel 0` loop.
+                let prologue_pc = instructions.len();
+                instructions.extend(proof_mode_prologue(prologue_pc, pc, builtin_bases.len()));
+                let initial_fp = execution_base + M31(2);
+                let frame = [initial_fp.into(), M31::zero().into()]
+                    .into_iter()
+                    .chain(builtin_pointers)
+                    .collect();
+                let final_pc = program_base + M31(u32_from_usize(prologue_pc + 2));
+                (frame, initial_fp, initial_fp, final_pc, prologue_pc)
+            } else {
+                // The builtin pointers, the arguments, then the final `fp`, `pc`.
+                let final_fp = memory.add_segment(SegmentKind::ReturnTarget);
+                let final_pc = memory.add_segment(SegmentKind::ReturnTarget);
+                memory.insert_all([final_fp, final_pc].map(|address| (address, QM31::zero())))?;
+                let frame: Vec<_> = builtin_pointers
+                    .chain(args.iter().copied())
+                    .chain([final_fp.into(), final_pc.into()])
+                    .collect();
+                let initial_fp = execution_base + M31(u32_from_usize(frame.len()));
+                (frame, initial_fp, final_fp, final_pc, pc)
+            };
 
         // Prepare memory.
 
-        // Segment 0: program.
-        let program_memory_segment =
-            program
-                .instructions
-                .iter()
-                .enumerate()
-                .map(|(index, instruction)| {
-                    let args = instruction.args;
-                    let encoded_instruction =
-                        QM31::from_m31_array([instruction.op, args[0], args[1], args[2]]);
-                    let instruction_address =
-                        Relocatable::from((program_segment, u32_from_usize(index)));
-
-                    (instruction_address, encoded_instruction)
-                });
-        let mut memory = Memory::from_iter(program_memory_segment);
-
-        // Segment 1: execution.
-        let execution_memory_segment = [
-            // Pointer to output cell.
-            ((execution_segment, 0), (output_segment, 0)),
-            // Final `fp`, `pc`; we never return from main.
-            ((execution_segment, 1), Self::FINAL_FP),
-            ((execution_segment, 2), Self::FINAL_PC),
-        ]
-        .map(|(address, value)| (Relocatable::from(address), Relocatable::from(value)));
-        memory.extend(execution_memory_segment);
-
-        // Segments 3, 4: write final `fp`, `pc`.
-        let final_pointers = [
-            (Self::FINAL_FP, QM31::zero()),
-            (Self::FINAL_PC, QM31::zero()),
-        ]
-        .map(|(address, value)| (Relocatable::from(address), value));
-        memory.extend(final_pointers);
+        // Program.
+        let program_memory_segment = instructions.iter().enumerate().map(|(index, instruction)| {
+            let args = instruction.args;
+            let encoded_instruction =
+                QM31::from_m31_array([instruction.op, args[0], args[1], args[2]]);
+            let instruction_address = program_base + M31(u32_from_usize(index));
+
+            (instruction_address, encoded_instruction)
+        });
+        memory.insert_all(program_memory_segment)?;
+        let decoded_program = DecodedProgram::new(&memory, program_base.segment);
+
+        // Execution.
+        let frame_size = u32_from_usize(frame.len());
+        memory.insert_all((0..).map(|offset| execution_base + M31(offset)).zip(frame))?;
 
         // Prepare state.
 
-        let initial_stack = Relocatable::from((execution_segment, 3));
-        let pc = Relocatable::from((program_segment, 0));
+        let pc = program_base + M31(u32_from_usize(pc));
         let state = State {
-            ap: initial_stack.into(),
-            fp: initial_stack.into(),
+            ap: initial_fp.into(),
+            fp: initial_fp.into(),
             pc: pc.into(),
         };
 
+        // The shortest name at each pc, which is the function rather than a label inside it.
+        let mut function_names = HashMap::<usize, String>::new();
+        for (name, &pc) in &program.identifiers {
+            match function_names.get(&pc) {
+                Some(other) if (other.len(), other) <= (name.len(), name) => {}
+                _ => {
+                    function_names.insert(pc, name.clone());
+                }
+            }
+        }
+
+        // The entry point returns at least the final builtin pointers, which are all it returns if
+        // the program doesn't define its return type.
+        let n_builtins = builtin_bases.len();
+        let return_size = function_names
+            .get(&entry_pc)
+            .and_then(|name| program.return_sizes.get(name))
+            .map_or(n_builtins, |&size| size.max(n_builtins));
+
         // Prepare hint runner.
-        let hint_runner = HintRunner::new(program.hints, input);
+        hint_runner.load(program_base, program.hints)?;
 
-        Self {
+        Ok(Self {
             memory,
             state,
             hint_runner,
-        }
+            trace: None,
+            relocated_trace: None,
+            relocation_table: None,
+            n_steps: 0,
+            max_steps: None,
+            loop_detector: None,
+            builtin_bases,
+            return_size,
+            final_fp,
+            final_pc,
+            proof_mode,
+            instruction_locations: program.instruction_locations,
+            function_names,
+            profiler: None,
+            decoded_program,
+            opcode_counts: vec![0; Opcode::ALL.len()],
+            max_ap: frame_size,
+        })
     }
 
-    fn step(&mut self) {
+    fn step(&mut self) -> Result<(), VmError> {
         self.hint_runner
-            .maybe_execute_hint(&mut self.memory, &self.state);
-        self.execute_instruction();
+            .maybe_execute_hints(&mut self.memory, &self.state)?;
+        self.decoded_program.refresh(&self.memory);
+        if let Some(loop_detector) = &mut self.loop_detector {
+            if loop_detector.is_stuck(self.state, &self.memory) {
+                return Err(VmError::InfiniteLoop { state: self.state });
+            }
+        }
+        if let Some(trace) = &mut self.trace {
+            trace.push(self.state);
+        }
+        self.execute_instruction()?;
+        self.n_steps += 1;
+        if let MaybeRelocatable::Relocatable(ap) = self.state.ap {
+            self.max_ap = self.max_ap.max(ap.offset.0);
+        }
+
+        Ok(())
     }
 
-    fn execute_instruction(&mut self) {
-        let MaybeRelocatable::Absolute(instruction) = self.memory[self.state.pc] else {
-            panic!("Instruction must be an absolute value.");
+    fn execute_instruction(&mut self) -> Result<(), VmError> {
+        let state = self.state;
+        let decoded = match self.decoded_program.get(state.pc) {
+            Some(decoded) => decoded,
+            // Outside the program segment, or an invalid instruction: decode from memory.
+            None => {
+                let value = self.memory.get(state.pc);
+                let Some(MaybeRelocatable::Absolute(instruction)) = value else {
+                    return Err(VmError::InvalidInstruction { state, value });
+                };
+                let instruction = Instruction::from(instruction);
+                DecodedInstruction::decode(instruction)
+                    .map_err(|error| error.with_context(state, instruction))?
+            }
         };
-        let Instruction { op, args } = instruction.into();
-        let instruction_fn = opcode_to_instruction(op);
+        let DecodedInstruction {
+            opcode,
+            handler,
+            instruction,
+        } = decoded;
 
-        self.state = instruction_fn(&mut self.memory, self.state, args);
+        self.state = handler(&mut self.memory, state, instruction.args)
+            .map_err(|error| error.with_context(state, instruction))?;
+        self.opcode_counts[opcode as usize] += 1;
+        if let Some(profiler) = &mut self.profiler {
+            profiler.record(opcode, self.state.pc);
+        }
+
+        Ok(())
+    }
+
+    /// Runs until the entry point returns. Errors carry the source location of the failing
+    /// instruction and the call stack leading to it.
+    pub fn execute(&mut self) -> Result<(), VmException> {
+        self.execute_until(usize::MAX).map(drop)
+    }
+
+    /// Like `execute`, but pauses once `n_steps` instructions were executed since the start of the
+    /// run, e.g. to take a snapshot. Returns whether the run ended.
+    pub fn execute_until(&mut self, n_steps: usize) -> Result<bool, VmException> {
+        self.run_until(n_steps).map_err(|error| VmException {
+            error: Box::new(error),
+            location: self.location(self.state.pc).cloned(),
+            traceback: self.traceback(),
+        })
     }
 
-    pub fn execute(&mut self) {
-        let [final_fp, final_pc] =
-            [Self::FINAL_FP, Self::FINAL_PC].map(|x| MaybeRelocatableAddr::Relocatable(x.into()));
+    fn run_until(&mut self, n_steps: usize) -> Result<bool, VmError> {
+        let [final_fp, final_pc] = [self.final_fp, self.final_pc].map(MaybeRelocatableAddr::from);
 
         while self.state.pc != final_pc {
-            self.step();
+            if self.n_steps >= n_steps {
+                return Ok(false);
+            }
+            if let Some(max_steps) = self
+                .max_steps
+                .filter(|&max_steps| self.n_steps >= max_steps)
+            {
+                return Err(VmError::StepLimitExceeded {
+                    state: self.state,
+                    max_steps,
+                });
+            }
+            self.step()?;
         }
 
-        assert_eq!(
-            self.state.fp, final_fp,
-            "Only final `fp` is allowed when at final `pc`."
-        );
+        if self.state.fp != final_fp {
+            return Err(VmError::UnexpectedFinalFp { state: self.state });
+        }
+        if self.proof_mode {
+            return self.pad_to_power_of_two(n_steps);
+        }
+
+        Ok(true)
+    }
+
+    /// Runs the final `jmp rel 0` loop of a proof-mode run until the number of steps is a power
+    /// of two, pausing after `n_steps` steps. Returns whether the padding is complete.
+    fn pad_to_power_of_two(&mut self, n_steps: usize) -> Result<bool, VmError> {
+        if let Some(max_steps) = self
+            .max_steps
+            .filter(|&max_steps| self.n_steps.next_power_of_two() > max_steps)
+        {
+            return Err(VmError::StepLimitExceeded {
+                state: self.state,
+                max_steps,
+            });
+        }
+        // The loop makes no progress on purpose.
+        let loop_detector = self.loop_detector.take();
+        let mut result = Ok(());
+        while result.is_ok() && !self.n_steps.is_power_of_two() && self.n_steps < n_steps {
+            result = self.step();
+        }
+        self.loop_detector = loop_detector;
+
+        result.map(|()| self.n_steps.is_power_of_two())
+    }
+
+    /// The source location of the instruction at `pc`, if the program has debug info.
+    pub fn location(&self, pc: MaybeRelocatableAddr) -> Option<&InstructionLocation> {
+        self.instruction_locations.get(&self.program_offset(pc)?)
+    }
+
+    /// The offset of `pc` in the program, if it points to the program segment.
+    fn program_offset(&self, pc: MaybeRelocatableAddr) -> Option<usize> {
+        let MaybeRelocatable::Relocatable(pc) = pc else {
+            return None;
+        };
+        if self.memory.segment_kinds().get(pc.segment) != Some(&SegmentKind::Program) {
+            return None;
+        }
+        Some(usize_from_u32(pc.offset.0))
+    }
+
+    /// The calls leading to the current frame, outermost first, found by following the return
+    /// `fp` and `pc` stored below each frame.
+    pub fn traceback(&self) -> Vec<CallSite> {
+        let mut call_sites = vec![];
+        let mut fp = self.state.fp;
+        while let MaybeRelocatable::Relocatable(frame) = fp {
+            let (
+                Some(MaybeRelocatable::Relocatable(return_fp)),
+                Some(MaybeRelocatable::Relocatable(return_pc)),
+            ) = (self.memory.get(fp - M31(2)), self.memory.get(fp - M31(1)))
+            else {
+                break;
+            };
+            // Stop at the entry point, or at a corrupted frame that does not go down the stack.
+            if return_pc == self.final_pc
+                || return_fp.segment != frame.segment
+                || return_fp.offset.0 >= frame.offset.0
+            {
+                break;
+            }
+            // Calls are a single instruction, right before the return `pc`.
+            let pc = return_pc - M31(1);
+            call_sites.push(CallSite {
+                pc,
+                location: self.location(pc.into()).cloned(),
+            });
+            fp = return_fp.into();
+        }
+        call_sites.reverse();
+
+        call_sites
     }
 }
 
 // Utils.
 
-type InstructionFn = fn(&mut Memory, State, InstructionArgs) -> State;
-
-// TODO(alont): autogenerate this.
-// TODO: optimize order.
-fn opcode_to_instruction(opcode: M31) -> InstructionFn {
-    match opcode.0 {
-        0 => addap_add_ap_ap,
-        1 => addap_add_ap_fp,
-        2 => addap_add_fp_ap,
-        3 => addap_add_fp_fp,
-        4 => addap_add_imm_ap,
-        5 => addap_add_imm_fp,
-        6 => addap_deref_ap,
-        7 => addap_deref_fp,
-        8 => addap_double_deref_ap,
-        9 => addap_double_deref_fp,
-        10 => addap_imm,
-        11 => addap_mul_ap_ap,
-        12 => addap_mul_ap_fp,
-        13 => addap_mul_fp_ap,
-        14 => addap_mul_fp_fp,
-        15 => addap_mul_imm_ap,
-        16 => addap_mul_imm_fp,
-        17 => assert_ap_add_ap_ap,
-        18 => assert_ap_add_ap_ap_appp,
-        19 => assert_ap_add_ap_fp,
-        20 => assert_ap_add_ap_fp_appp,
-        21 => assert_ap_add_fp_ap,
-        22 => assert_ap_add_fp_ap_appp,
-        23 => assert_ap_add_fp_fp,
-        24 => assert_ap_add_fp_fp_appp,
-        25 => assert_ap_add_imm_ap,
-        26 => assert_ap_add_imm_ap_appp,
-        27 => assert_ap_add_imm_fp,
-        28 => assert_ap_add_imm_fp_appp,
-        29 => assert_ap_deref_ap,
-        30 => assert_ap_deref_ap_appp,
-        31 => assert_ap_deref_fp,
-        32 => assert_ap_deref_fp_appp,
-        33 => assert_ap_double_deref_ap,
-        34 => assert_ap_double_deref_ap_appp,
-        35 => assert_ap_double_deref_fp,
-        36 => assert_ap_double_deref_fp_appp,
-        37 => assert_ap_imm,
-        38 => assert_ap_imm_appp,
-        39 => assert_ap_mul_ap_ap,
-        40 => assert_ap_mul_ap_ap_appp,
-        41 => assert_ap_mul_ap_fp,
-        42 => assert_ap_mul_ap_fp_appp,
-        43 => assert_ap_mul_fp_ap,
-        44 => assert_ap_mul_fp_ap_appp,
-        45 => assert_ap_mul_fp_fp,
-        46 => assert_ap_mul_fp_fp_appp,
-        47 => assert_ap_mul_imm_ap,
-        48 => assert_ap_mul_imm_ap_appp,
-        49 => assert_ap_mul_imm_fp,
-        50 => assert_ap_mul_imm_fp_appp,
-        51 => assert_fp_add_ap_ap,
-        52 => assert_fp_add_ap_ap_appp,
-        53 => assert_fp_add_ap_fp,
-        54 => assert_fp_add_ap_fp_appp,
-        55 => assert_fp_add_fp_ap,
-        56 => assert_fp_add_fp_ap_appp,
-        57 => assert_fp_add_fp_fp,
-        58 => assert_fp_add_fp_fp_appp,
-        59 => assert_fp_add_imm_ap,
-        60 => assert_fp_add_imm_ap_appp,
-        61 => assert_fp_add_imm_fp,
-        62 => assert_fp_add_imm_fp_appp,
-        63 => assert_fp_deref_ap,
-        64 => assert_fp_deref_ap_appp,
-        65 => assert_fp_deref_fp,
-        66 => assert_fp_deref_fp_appp,
-        67 => assert_fp_double_deref_ap,
-        68 => assert_fp_double_deref_ap_appp,
-        69 => assert_fp_double_deref_fp,
-        70 => assert_fp_double_deref_fp_appp,
-        71 => assert_fp_imm,
-        72 => assert_fp_imm_appp,
-        73 => assert_fp_mul_ap_ap,
-        74 => assert_fp_mul_ap_ap_appp,
-        75 => assert_fp_mul_ap_fp,
-        76 => assert_fp_mul_ap_fp_appp,
-        77 => assert_fp_mul_fp_ap,
-        78 => assert_fp_mul_fp_ap_appp,
-        79 => assert_fp_mul_fp_fp,
-        80 => assert_fp_mul_fp_fp_appp,
-        81 => assert_fp_mul_imm_ap,
-        82 => assert_fp_mul_imm_ap_appp,
-        83 => assert_fp_mul_imm_fp,
-        84 => assert_fp_mul_imm_fp_appp,
-        85 => call_abs_ap,
-        86 => call_abs_fp,
-        87 => call_abs_imm,
-        88 => call_rel_ap,
-        89 => call_rel_fp,
-        90 => call_rel_imm,
-        91 => jmp_abs_add_ap_ap,
-        92 => jmp_abs_add_ap_ap_appp,
-        93 => jmp_abs_add_ap_fp,
-        94 => jmp_abs_add_ap_fp_appp,
-        95 => jmp_abs_add_fp_ap,
-        96 => jmp_abs_add_fp_ap_appp,
-        97 => jmp_abs_add_fp_fp,
-        98 => jmp_abs_add_fp_fp_appp,
-        99 => jmp_abs_add_imm_ap,
-        100 => jmp_abs_add_imm_ap_appp,
-        101 => jmp_abs_add_imm_fp,
-        102 => jmp_abs_add_imm_fp_appp,
-        103 => jmp_abs_deref_ap,
-        104 => jmp_abs_deref_ap_appp,
-        105 => jmp_abs_deref_fp,
-        106 => jmp_abs_deref_fp_appp,
-        107 => jmp_abs_double_deref_ap,
-        108 => jmp_abs_double_deref_ap_appp,
-        109 => jmp_abs_double_deref_fp,
-        110 => jmp_abs_double_deref_fp_appp,
-        111 => jmp_abs_imm,
-        112 => jmp_abs_imm_appp,
-        113 => jmp_abs_mul_ap_ap,
-        114 => jmp_abs_mul_ap_ap_appp,
-        115 => jmp_abs_mul_ap_fp,
-        116 => jmp_abs_mul_ap_fp_appp,
-        117 => jmp_abs_mul_fp_ap,
-        118 => jmp_abs_mul_fp_ap_appp,
-        119 => jmp_abs_mul_fp_fp,
-        120 => jmp_abs_mul_fp_fp_appp,
-        121 => jmp_abs_mul_imm_ap,
-        122 => jmp_abs_mul_imm_ap_appp,
-        123 => jmp_abs_mul_imm_fp,
-        124 => jmp_abs_mul_imm_fp_appp,
-        125 => jmp_rel_add_ap_ap,
-        126 => jmp_rel_add_ap_ap_appp,
-        127 => jmp_rel_add_ap_fp,
-        128 => jmp_rel_add_ap_fp_appp,
-        129 => jmp_rel_add_fp_ap,
-        130 => jmp_rel_add_fp_ap_appp,
-        131 => jmp_rel_add_fp_fp,
-        132 => jmp_rel_add_fp_fp_appp,
-        133 => jmp_rel_add_imm_ap,
-        134 => jmp_rel_add_imm_ap_appp,
-        135 => jmp_rel_add_imm_fp,
-        136 => jmp_rel_add_imm_fp_appp,
-        137 => jmp_rel_deref_ap,
-        138 => jmp_rel_deref_ap_appp,
-        139 => jmp_rel_deref_fp,
-        140 => jmp_rel_deref_fp_appp,
-        141 => jmp_rel_double_deref_ap,
-        142 => jmp_rel_double_deref_ap_appp,
-        143 => jmp_rel_double_deref_fp,
-        144 => jmp_rel_double_deref_fp_appp,
-        145 => jmp_rel_imm,
-        146 => jmp_rel_imm_appp,
-        147 => jmp_rel_mul_ap_ap,
-        148 => jmp_rel_mul_ap_ap_appp,
-        149 => jmp_rel_mul_ap_fp,
-        150 => jmp_rel_mul_ap_fp_appp,
-        151 => jmp_rel_mul_fp_ap,
-        152 => jmp_rel_mul_fp_ap_appp,
-        153 => jmp_rel_mul_fp_fp,
-        154 => jmp_rel_mul_fp_fp_appp,
-        155 => jmp_rel_mul_imm_ap,
-        156 => jmp_rel_mul_imm_ap_appp,
-        157 => jmp_rel_mul_imm_fp,
-        158 => jmp_rel_mul_imm_fp_appp,
-        159 => jnz_ap_ap,
-        160 => jnz_ap_ap_appp,
-        161 => jnz_ap_fp,
-        162 => jnz_ap_fp_appp,
-        163 => jnz_fp_ap,
-        164 => jnz_fp_ap_appp,
-        165 => jnz_fp_fp,
-        166 => jnz_fp_fp_appp,
-        167 => jnz_imm_ap,
-        168 => jnz_imm_ap_appp,
-        169 => jnz_imm_fp,
-        170 => jnz_imm_fp_appp,
-        171 => ret,
-        _ => panic!("Unknown opcode: {}.", opcode),
-    }
+/// The proof-mode prologue at `prologue_pc`: `ap += n_builtins; call rel <entry_pc>; jmp rel 0`.
+fn proof_mode_prologue(prologue_pc: usize, entry_pc: usize, n_builtins: usize) -> [Instruction; 3] {
+    let call_pc = u32_from_usize(prologue_pc + 1);
+    let call_offset = M31(u32_from_usize(entry_pc)) - M31(call_pc);
+    [
+        Instruction::from([
+            Opcode::AddapImm.into(),
+            M31(u32_from_usize(n_builtins)),
+            M31(0),
+            M31(0),
+        ]),
+        Instruction::from([Opcode::CallRelImm.into(), call_offset, M31(0), M31(0)]),
+        Instruction::from([Opcode::JmpRelImm.into(), M31(0), M31(0), M31(0)]),
+    ]
+}
+
+pub(crate) type InstructionResult = Result<State, InstructionError>;
+
+pub(crate) type InstructionFn = fn(&mut Memory, State, InstructionArgs) -> InstructionResult;
+
+/// Executes an instruction from the operation and the ap update of its opcode. The handler of
+/// every opcode calls this with its entry in the opcode table, so the two can't disagree.
+#[inline(always)]
+pub(crate) fn execute_operation(
+    operation: Operation,
+    ap_update: ApUpdate,
+    memory: &mut Memory,
+    state: State,
+    args: InstructionArgs,
+) -> InstructionResult {
+    // `ap += operand` and the `ap += 2` of a call are part of their operations.
+    let next_state = match operation {
+        Operation::AddAp(operand) => add_ap::addap(memory, state, operand, &args)?,
+        Operation::Assert(dest, operand) => {
+            assert::assert(memory, state, dest, operand, &args)?;
+            state.advance()
+        }
+        Operation::Call(mode, target) => call::call(memory, state, mode, target, &args)?,
+        Operation::Jmp(mode, operand) => jmp::jmp(memory, state, mode, operand, &args)?,
+        Operation::Jnz(target, cond) => jnz::jnz(memory, state, target, cond, &args)?,
+        Operation::Ret => call::ret(memory, state)?,
+    };
+
+    Ok(match ap_update {
+        ApUpdate::Add1 => State {
+            ap: next_state.ap + M31(1),
+            ..next_state
+        },
+        ApUpdate::Regular | ApUpdate::Add | ApUpdate::Add2 => next_state,
+    })
 }
 
 pub(crate) fn resolve_addresses<const N: usize>(
     state: State,
-    bases: &[&str; N],
+    bases: &[Register; N],
     offsets: &[M31; N],
 ) -> [MaybeRelocatableAddr; N] {
-    assert!(
-        bases.len() <= 3,
-        "The number of bases and offsets should not exceed 3"
-    );
-
-    std::array::from_fn(|i| {
-        let base = bases[i];
-        let base_address = match base {
-            "ap" => state.ap,
-            "fp" => state.fp,
-            _ => panic!("Invalid base: {}", base),
-        };
-        base_address + offsets[i]
-    })
+    std::array::from_fn(|i| state.register(bases[i]) + offsets[i])
 }
 
-pub(crate) fn run_fibonacci() {
-    let program_path = get_tests_data_dir().join("fibonacci_compiled.json");
-    let program = Program::from_compiled_file(program_path);
-    let input = serde_json::json!({ "fibonacci_claim_index": ["0x64", "0x0", "0x0", "0x0"]});
-    let mut vm = VM::create_for_main_entry_point(program, input);
-
-    vm.execute();
+/// Reads a memory cell, which must already be known.
+pub(crate) fn read_memory(
+    memory: &Memory,
+    address: MaybeRelocatableAddr,
+) -> Result<MaybeRelocatableValue, InstructionError> {
+    memory
+        .get(address)
+        .ok_or(InstructionError::UnknownMemoryCell(address))
 }
 
 #[cfg(test)]
 mod test {
-    use crate::vm::run_fibonacci;
+    use stwo_prover::core::fields::m31::M31;
+    use stwo_prover::core::fields::qm31::QM31;
+
+    use crate::memory::relocatable::{MaybeRelocatable, Relocatable};
+    use crate::memory::{MaybeRelocatableValue, Memory, MemoryError};
+    use crate::test_utils::{fibonacci_input, fibonacci_program, fibonacci_vm};
+    use crate::vm::error::{ProgramError, VmError};
+    use crate::vm::hints::{HintError, HintProcessor, HintRunner};
+    use crate::vm::trace::TraceEntry;
+    use crate::vm::{Input, Program, State, VM};
+
+    fn run_instructions(instructions: &[[u32; 4]]) -> Result<(), VmError> {
+        let program = Program::from_instructions(instructions);
+        let mut vm =
+            VM::create_for_main_entry_point(program, HintRunner::new(Input::default())).unwrap();
+
+        vm.execute().map_err(|exception| *exception.error)
+    }
 
     #[test]
     fn test_runner() {
-        run_fibonacci()
+        let mut vm = fibonacci_vm(100);
+
+        vm.execute().unwrap();
+    }
+
+    #[test]
+    fn test_relocated_trace() {
+        let n_instructions = fibonacci_program().instructions.len();
+        let mut vm = fibonacci_vm(5);
+        vm.enable_trace_recording();
+
+        vm.execute().unwrap();
+        let n_steps = vm.trace().unwrap().len();
+        vm.relocate();
+
+        let trace = vm.relocated_trace().unwrap();
+        let execution_base = 1 + n_instructions as u64;
+        assert_eq!(trace.len(), n_steps);
+        assert_eq!(
+            trace[0],
+            TraceEntry {
+                ap: execution_base + 3,
+                fp: execution_base + 3,
+                pc: 1
+            }
+        );
+        assert!(trace
+            .iter()
+            .all(|entry| (1..1 + n_instructions as u64).contains(&entry.pc)));
+    }
+
+    #[test]
+    fn test_unknown_opcode() {
+        let error = run_instructions(&[[172, 0, 0, 0]]).unwrap_err();
+
+        let VmError::UnknownOpcode { state, instruction } = error else {
+            panic!("Unexpected error: {error}");
+        };
+        assert_eq!(state.pc, Relocatable::from((0, 0)).into());
+        assert_eq!(instruction.op, M31(172));
+    }
+
+    #[test]
+    fn test_failed_assertion() {
+        // `[ap] = 1; [ap] = 2`.
+        let error = run_instructions(&[[37, 0, 1, 0], [37, 0, 2, 0]]).unwrap_err();
+
+        let VmError::AssertionFailed {
+            state,
+            expected,
+            actual,
+            ..
+        } = error
+        else {
+            panic!("Unexpected error: {error}");
+        };
+        assert_eq!(state.pc, Relocatable::from((0, 1)).into());
+        assert_eq!(*expected, MaybeRelocatable::Absolute(M31(1).into()));
+        assert_eq!(*actual, MaybeRelocatable::Absolute(M31(2).into()));
+    }
+
+    #[test]
+    fn test_entry_point() {
+        let program = fibonacci_program();
+        let fib_pc = program.identifiers["__main__.fib"];
+
+        // `fib(first_element=1, second_element=1, n=3)`.
+        let args = [1, 1, 3].map(|value| MaybeRelocatableValue::from(M31(value)));
+        let mut vm = VM::create_for_entry_point(
+            program,
+            "__main__.fib",
+            &args,
+            HintRunner::new(Input::default()),
+        )
+        .unwrap();
+
+        let pc = Relocatable::from((0, fib_pc as u32)).into();
+        assert_eq!(vm.state().pc(), pc);
+
+        vm.execute().unwrap();
+
+        // 1, 1 -> 1, 2 -> 2, 5 -> 5, 29.
+        assert_eq!(vm.return_values(1), Ok(vec![M31(29).into()]));
+        assert!(matches!(
+            vm.return_values(100),
+            Err(VmError::MissingReturnValue { .. })
+        ));
+    }
+
+    #[test]
+    fn test_unknown_entry_point() {
+        let error = VM::create_for_entry_point(
+            fibonacci_program(),
+            "__main__.foo",
+            &[],
+            HintRunner::new(Input::default()),
+        )
+        .unwrap_err();
+
+        assert!(matches!(error, ProgramError::UnknownEntryPoint(name) if name == "__main__.foo"));
+    }
+
+    #[test]
+    fn test_entry_point_argument_in_unknown_segment() {
+        let program = fibonacci_program();
+        let args = [Relocatable::from((100, 0)).into()];
+
+        let error = VM::create_for_entry_point(
+            program,
+            "__main__.fib",
+            &args,
+            HintRunner::new(Input::default()),
+        )
+        .unwrap_err();
+
+        assert!(matches!(
+            error,
+            ProgramError::Memory(MemoryError::UnknownSegment(100))
+        ));
+    }
+
+    #[test]
+    fn test_invalid_program_hex() {
+        let bad_word = serde_json::json!({ "data": [["0x25", "0x0", "0xg", "0x0"]], "hints": {} });
+        let unreduced_word =
+            serde_json::json!({ "data": [["0x25", "0x0", "0x7fffffff", "0x0"]], "hints": {} });
+        let bad_hint_pc = serde_json::json!({
+            "data": [["0x25", "0x0", "0x1", "0x0"]],
+            "hints": { "g": [{ "code": "hint" }] },
+        });
+
+        for raw_program in [bad_word, unreduced_word, bad_hint_pc] {
+            let error = serde_json::from_value::<Program>(raw_program).unwrap_err();
+            assert!(error.to_string().starts_with("Invalid"), "{error}");
+        }
+    }
+
+    #[test]
+    fn test_proof_mode() {
+        let program = fibonacci_program();
+        let prologue_pc = program.instructions.len() as u32;
+        let input = fibonacci_input(5);
+        let mut vm =
+            VM::create_for_proof_mode(program, "__main__.main", HintRunner::new(input)).unwrap();
+        vm.enable_trace_recording();
+        vm.enable_loop_detection();
+
+        vm.execute().unwrap();
+
+        // 3 prologue steps and 52 steps in `main`, padded with 9 iterations of the final loop.
+        assert_eq!(vm.n_steps(), 64);
+        let trace = vm.trace().unwrap();
+        assert_eq!(trace[0].pc, Relocatable::from((0, prologue_pc)).into());
+        let final_state = *vm.state();
+        assert_eq!(trace[63], final_state);
+        assert_eq!(
+            final_state.pc,
+            Relocatable::from((0, prologue_pc + 2)).into()
+        );
+        assert_eq!(final_state.fp, Relocatable::from((1, 2)).into());
+        assert_eq!(vm.output().unwrap()[0], M31(5).into());
+
+        // The stack of the prologue, with the output pointer, and the final output pointer.
+        let MaybeRelocatable::Relocatable(ap) = final_state.ap else {
+            panic!("`ap` must be a relocatable value.");
+        };
+        let public_cells = [(1, 0), (1, 1), (1, 2)].map(Relocatable::from);
+        assert_eq!(
+            vm.public_execution_cells(),
+            [public_cells.as_slice(), &[ap - M31(1)]].concat()
+        );
+    }
+
+    #[test]
+    fn test_proof_mode_step_limit() {
+        let mut vm = VM::create_for_proof_mode(
+            fibonacci_program(),
+            "__main__.main",
+            HintRunner::new(fibonacci_input(5)),
+        )
+        .unwrap();
+        // The run reaches its final loop after 54 steps, and is padded to 64.
+        vm.set_max_steps(60);
+
+        let error = *vm.execute().unwrap_err().error;
+
+        assert!(matches!(
+            error,
+            VmError::StepLimitExceeded { max_steps: 60, .. }
+        ));
+        assert_eq!(vm.n_steps(), 54);
+    }
+
+    #[test]
+    fn test_step_limit() {
+        // `jmp rel 0`.
+        let program = Program::from_instructions(&[[145, 0, 0, 0]]);
+        let mut vm =
+            VM::create_for_main_entry_point(program, HintRunner::new(Input::default())).unwrap();
+        vm.set_max_steps(10);
+
+        let error = *vm.execute().unwrap_err().error;
+
+        assert!(matches!(
+            error,
+            VmError::StepLimitExceeded { max_steps: 10, .. }
+        ));
+        assert_eq!(vm.n_steps(), 10);
+    }
+
+    #[test]
+    fn test_loop_detection() {
+        // `jmp rel 1; jmp rel -1`.
+        let program = Program::from_instructions(&[[145, 1, 0, 0], [145, 0x7ffffffe, 0, 0]]);
+        let mut vm =
+            VM::create_for_main_entry_point(program, HintRunner::new(Input::default())).unwrap();
+        vm.enable_loop_detection();
+
+        let error = *vm.execute().unwrap_err().error;
+
+        let VmError::InfiniteLoop { state } = error else {
+            panic!("Unexpected error: {error}");
+        };
+        assert_eq!(state.pc, Relocatable::from((0, 0)).into());
+        assert_eq!(vm.n_steps(), 2);
+    }
+
+    #[test]
+    fn test_loop_detection_same_value_write() {
+        // `[ap] = 5; jmp rel -1`, which rewrites the same value on every iteration.
+        let program = Program::from_instructions(&[[37, 0, 5, 0], [145, 0x7ffffffe, 0, 0]]);
+        let mut vm =
+            VM::create_for_main_entry_point(program, HintRunner::new(Input::default())).unwrap();
+        vm.enable_loop_detection();
+
+        let error = *vm.execute().unwrap_err().error;
+
+        let VmError::InfiniteLoop { state } = error else {
+            panic!("Unexpected error: {error}");
+        };
+        assert_eq!(state.pc, Relocatable::from((0, 1)).into());
+        assert_eq!(vm.n_steps(), 3);
+    }
+
+    #[test]
+    fn test_loop_detection_fibonacci() {
+        let mut vm = fibonacci_vm(5);
+        vm.enable_loop_detection();
+
+        vm.execute().unwrap();
+    }
+
+    #[test]
+    fn test_deduce_operand_multiplied_by_zero() {
+        // `[ap] = 5; [ap] = 0 * [ap + 1]`.
+        let error = run_instructions(&[[37, 0, 5, 0], [47, 0, 0, 1]]).unwrap_err();
+
+        let VmError::DeductionFailed { state, .. } = error else {
+            panic!("Unexpected error: {error}");
+        };
+        assert_eq!(state.pc, Relocatable::from((0, 1)).into());
+    }
+
+    #[test]
+    fn test_unknown_memory_cell() {
+        // `jmp rel [fp]`, where `[fp]` was never written.
+        let error = run_instructions(&[[139, 0, 0, 0]]).unwrap_err();
+
+        assert!(matches!(error, VmError::UnknownMemoryCell { .. }));
+    }
+
+    #[test]
+    fn test_absolute_pc() {
+        // `jmp abs 5`, which leaves `pc` outside of any segment.
+        let error = run_instructions(&[[111, 5, 0, 0]]).unwrap_err();
+
+        let VmError::NonRelocatablePc { state } = error else {
+            panic!("Unexpected error: {error}");
+        };
+        assert_eq!(state.pc, MaybeRelocatable::Absolute(M31(5)));
+    }
+
+    #[test]
+    fn test_address_out_of_range() {
+        // `[ap] = 0x40000001, ap++; [ap] = 5, ap++; [ap-1] = [[ap-2]]`, where the last instruction
+        // deduces the cell at the absolute address 2 ** 30 + 1.
+        let error = run_instructions(&[
+            [38, 0, 0x40000001, 0],
+            [38, 0, 5, 0],
+            [33, 0x7ffffffe, 0x7ffffffd, 0],
+        ])
+        .unwrap_err();
+
+        let VmError::Memory { state, error, .. } = error else {
+            panic!("Unexpected error: {error}");
+        };
+        assert_eq!(state.pc, Relocatable::from((0, 2)).into());
+        assert_eq!(*error, MemoryError::AddressOutOfRange(M31(0x40000001)));
+    }
+
+    #[test]
+    fn test_inconsistent_write() {
+        // `[ap] = 5; call rel 1`, where the call pushes `fp` to `[ap]`.
+        let error = run_instructions(&[[37, 0, 5, 0], [90, 1, 0, 0]]).unwrap_err();
+
+        let VmError::Memory { state, error, .. } = error else {
+            panic!("Unexpected error: {error}");
+        };
+        let MemoryError::InconsistentWrite { existing, .. } = *error else {
+            panic!("Unexpected error: {error}");
+        };
+        assert_eq!(state.pc, Relocatable::from((0, 1)).into());
+        assert_eq!(existing, MaybeRelocatable::Absolute(M31(5).into()));
+    }
+
+    /// Overwrites the instruction after `pc` with `ret`.
+    #[derive(Debug)]
+    struct PatchNextInstruction;
+
+    impl HintProcessor for PatchNextInstruction {
+        fn execute(&self, memory: &mut Memory, state: &State, _: &Input) -> Result<(), HintError> {
+            let ret = QM31::from_m31_array([171, 0, 0, 0].map(M31));
+            memory.overwrite(state.pc + M31(1), ret)?;
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_overwritten_instruction() {
+        // `[ap] = 5; [ap] = 6; ret`, where a hint replaces `[ap] = 6` with `ret`.
+        let program = Program {
+            hints: vec![vec!["patch".to_string()]],
+            ..Program::from_instructions(&[[37, 0, 5, 0], [37, 0, 6, 0], [171, 0, 0, 0]])
+        };
+        let mut hint_runner = HintRunner::new(Input::default());
+        hint_runner.register("patch", PatchNextInstruction);
+        let mut vm = VM::create_for_main_entry_point(program, hint_runner).unwrap();
+
+        vm.execute().unwrap();
+
+        assert_eq!(vm.n_steps(), 2);
+    }
+
+    #[test]
+    fn test_error_location() {
+        let mut vm =
+            VM::create_for_main_entry_point(fibonacci_program(), HintRunner::new(Input::default()))
+                .unwrap();
+
+        // The hint before `pc` 1 is missing its input.
+        let exception = vm.execute().unwrap_err();
+
+        assert!(matches!(*exception.error, VmError::Hint { .. }));
+        let location = exception.location.unwrap();
+        assert_eq!(
+            location.inst.input_file.filename.unwrap(),
+            "fibonacci.cairo"
+        );
+        assert_eq!((location.inst.start_line, location.inst.start_col), (8, 5));
+        assert_eq!(location.accessible_scopes, ["__main__", "__main__.main"]);
+        assert!(exception.traceback.is_empty());
+    }
+
+    #[test]
+    fn test_traceback() {
+        let mut vm = fibonacci_vm(5);
+        // Stop at the start of the third recursive call of `fib`.
+        vm.set_max_steps(20);
+
+        let exception = vm.execute().unwrap_err();
+
+        let location = exception.location.as_ref().unwrap();
+        assert_eq!(location.inst.start_line, 18);
+        assert_eq!(location.accessible_scopes.last().unwrap(), "__main__.fib");
+        let call_pcs: Vec<_> = exception
+            .traceback
+            .iter()
+            .map(|call_site| call_site.pc.offset.0)
+            .collect();
+        assert_eq!(call_pcs, [5, 17, 17]);
+        let message = exception.to_string();
+        assert!(message.starts_with("fibonacci.cairo:18:5 (__main__, __main__.fib): Step limit"));
+        assert!(message.contains("\n    fibonacci.cairo:10:15 (__main__, __main__.main) (pc 0:5)"));
     }
 }
@@ -0,0 +1,391 @@
+use std::fmt;
+
+use paste::paste;
+use stwo_prover::core::fields::m31::M31;
+
+use super::error::InstructionError;
+use super::{execute_operation, InstructionFn};
+
+/// A register that memory cells are addressed relative to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Register {
+    Ap,
+    Fp,
+}
+
+impl Register {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Ap => "ap",
+            Self::Fp => "fp",
+        }
+    }
+}
+
+/// An operand, with the arguments layout of the functions in `vm::operand`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Operand {
+    /// `args[0]`.
+    Imm,
+    /// `[reg+args[0]]`.
+    Deref(Register),
+    /// `[[reg+args[0]]+args[1]]`.
+    DoubleDeref(Register),
+    /// `[lhs+args[0]] + [rhs+args[1]]`.
+    Add(Register, Register),
+    /// `[reg+args[1]] + args[0]`.
+    AddImm(Register),
+    /// `[lhs+args[0]] * [rhs+args[1]]`.
+    Mul(Register, Register),
+    /// `[reg+args[1]] * args[0]`.
+    MulImm(Register),
+}
+
+/// Whether a jump or call is to an absolute address or relative to `pc`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum JumpMode {
+    Abs,
+    Rel,
+}
+
+/// What an opcode does.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Operation {
+    /// `ap += operand`.
+    AddAp(Operand),
+    /// `[dest+args[0]] = operand`, with the operand arguments starting at `args[1]`.
+    Assert(Register, Operand),
+    /// `call abs/rel target`, where the target is `Imm` or `Deref`.
+    Call(JumpMode, Operand),
+    /// `jmp abs/rel operand`.
+    Jmp(JumpMode, Operand),
+    /// `jmp rel target if [cond+args[1]] != 0`, where the target is `Imm` or `Deref`.
+    Jnz(Operand, Register),
+    Ret,
+}
+
+/// How an opcode updates `ap`, in addition to the operation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ApUpdate {
+    Regular,
+    /// `ap += operand`.
+    Add,
+    /// `ap++`.
+    Add1,
+    /// `ap += 2`, by pushing the return `fp` and `pc` in a call.
+    Add2,
+}
+
+macro_rules! define_opcodes {
+    ($($number:literal => $name:ident: $operation:expr, $ap_update:ident;)*) => {
+        paste! {
+            /// An opcode of the VM. Generated from the opcode table, with the handler of each
+            /// opcode built from its operation and ap update.
+            #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+            pub enum Opcode {
+                $([<$name:camel>] = $number,)*
+            }
+
+            impl Opcode {
+                /// All the opcodes, by number.
+                pub const ALL: [Self; [$($number),*].len()] = [$(Self::[<$name:camel>],)*];
+
+                pub fn name(self) -> &'static str {
+                    match self {
+                        $(Self::[<$name:camel>] => stringify!($name),)*
+                    }
+                }
+
+                pub fn operation(self) -> Operation {
+                    use JumpMode::*;
+                    use Operand::*;
+                    use Operation::*;
+                    use Register::*;
+
+                    match self {
+                        $(Self::[<$name:camel>] => $operation,)*
+                    }
+                }
+
+                pub fn ap_update(self) -> ApUpdate {
+                    match self {
+                        $(Self::[<$name:camel>] => ApUpdate::$ap_update,)*
+                    }
+                }
+
+                /// Executes the operation and the ap update of the opcode.
+                pub(crate) fn handler(self) -> InstructionFn {
+                    use JumpMode::*;
+                    use Operand::*;
+                    use Operation::*;
+                    use Register::*;
+
+                    match self {
+                        $(Self::[<$name:camel>] => |memory, state, args| {
+                            execute_operation($operation, ApUpdate::$ap_update, memory, state, args)
+                        },)*
+                    }
+                }
+            }
+
+            impl TryFrom<M31> for Opcode {
+                type Error = InstructionError;
+
+                fn try_from(opcode: M31) -> Result<Self, Self::Error> {
+                    match opcode.0 {
+                        $($number => Ok(Self::[<$name:camel>]),)*
+                        _ => Err(InstructionError::UnknownOpcode(opcode)),
+                    }
+                }
+            }
+        }
+    };
+}
+
+impl Opcode {
+    /// The opcode with the given operation that increments `ap` if `increments_ap` is set.
+    pub fn from_operation(operation: Operation, increments_ap: bool) -> Option<Self> {
+        Self::ALL.into_iter().find(|opcode| {
+            opcode.operation() == operation
+                && (opcode.ap_update() == ApUpdate::Add1) == increments_ap
+        })
+    }
+
+    /// The name without the `_appp` suffix, as printed by the disassembler.
+    pub fn mnemonic(self) -> &'static str {
+        self.name().trim_end_matches("_appp")
+    }
+}
+
+impl From<Opcode> for M31 {
+    fn from(opcode: Opcode) -> Self {
+        M31(opcode as u32)
+    }
+}
+
+impl fmt::Display for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+// The opcode table: `number => name: operation, ap update;`, ordered by name.
+define_opcodes! {
+    0 => addap_add_ap_ap: AddAp(Add(Ap, Ap)), Add;
+    1 => addap_add_ap_fp: AddAp(Add(Ap, Fp)), Add;
+    2 => addap_add_fp_ap: AddAp(Add(Fp, Ap)), Add;
+    3 => addap_add_fp_fp: AddAp(Add(Fp, Fp)), Add;
+    4 => addap_add_imm_ap: AddAp(AddImm(Ap)), Add;
+    5 => addap_add_imm_fp: AddAp(AddImm(Fp)), Add;
+    6 => addap_deref_ap: AddAp(Deref(Ap)), Add;
+    7 => addap_deref_fp: AddAp(Deref(Fp)), Add;
+    8 => addap_double_deref_ap: AddAp(DoubleDeref(Ap)), Add;
+    9 => addap_double_deref_fp: AddAp(DoubleDeref(Fp)), Add;
+    10 => addap_imm: AddAp(Imm), Add;
+    11 => addap_mul_ap_ap: AddAp(Mul(Ap, Ap)), Add;
+    12 => addap_mul_ap_fp: AddAp(Mul(Ap, Fp)), Add;
+    13 => addap_mul_fp_ap: AddAp(Mul(Fp, Ap)), Add;
+    14 => addap_mul_fp_fp: AddAp(Mul(Fp, Fp)), Add;
+    15 => addap_mul_imm_ap: AddAp(MulImm(Ap)), Add;
+    16 => addap_mul_imm_fp: AddAp(MulImm(Fp)), Add;
+    17 => assert_ap_add_ap_ap: Assert(Ap, Add(Ap, Ap)), Regular;
+    18 => assert_ap_add_ap_ap_appp: Assert(Ap, Add(Ap, Ap)), Add1;
+    19 => assert_ap_add_ap_fp: Assert(Ap, Add(Ap, Fp)), Regular;
+    20 => assert_ap_add_ap_fp_appp: Assert(Ap, Add(Ap, Fp)), Add1;
+    21 => assert_ap_add_fp_ap: Assert(Ap, Add(Fp, Ap)), Regular;
+    22 => assert_ap_add_fp_ap_appp: Assert(Ap, Add(Fp, Ap)), Add1;
+    23 => assert_ap_add_fp_fp: Assert(Ap, Add(Fp, Fp)), Regular;
+    24 => assert_ap_add_fp_fp_appp: Assert(Ap, Add(Fp, Fp)), Add1;
+    25 => assert_ap_add_imm_ap: Assert(Ap, AddImm(Ap)), Regular;
+    26 => assert_ap_add_imm_ap_appp: Assert(Ap, AddImm(Ap)), Add1;
+    27 => assert_ap_add_imm_fp: Assert(Ap, AddImm(Fp)), Regular;
+    28 => assert_ap_add_imm_fp_appp: Assert(Ap, AddImm(Fp)), Add1;
+    29 => assert_ap_deref_ap: Assert(Ap, Deref(Ap)), Regular;
+    30 => assert_ap_deref_ap_appp: Assert(Ap, Deref(Ap)), Add1;
+    31 => assert_ap_deref_fp: Assert(Ap, Deref(Fp)), Regular;
+    32 => assert_ap_deref_fp_appp: Assert(Ap, Deref(Fp)), Add1;
+    33 => assert_ap_double_deref_ap: Assert(Ap, DoubleDeref(Ap)), Regular;
+    34 => assert_ap_double_deref_ap_appp: Assert(Ap, DoubleDeref(Ap)), Add1;
+    35 => assert_ap_double_deref_fp: Assert(Ap, DoubleDeref(Fp)), Regular;
+    36 => assert_ap_double_deref_fp_appp: Assert(Ap, DoubleDeref(Fp)), Add1;
+    37 => assert_ap_imm: Assert(Ap, Imm), Regular;
+    38 => assert_ap_imm_appp: Assert(Ap, Imm), Add1;
+    39 => assert_ap_mul_ap_ap: Assert(Ap, Mul(Ap, Ap)), Regular;
+    40 => assert_ap_mul_ap_ap_appp: Assert(Ap, Mul(Ap, Ap)), Add1;
+    41 => assert_ap_mul_ap_fp: Assert(Ap, Mul(Ap, Fp)), Regular;
+    42 => assert_ap_mul_ap_fp_appp: Assert(Ap, Mul(Ap, Fp)), Add1;
+    43 => assert_ap_mul_fp_ap: Assert(Ap, Mul(Fp, Ap)), Regular;
+    44 => assert_ap_mul_fp_ap_appp: Assert(Ap, Mul(Fp, Ap)), Add1;
+    45 => assert_ap_mul_fp_fp: Assert(Ap, Mul(Fp, Fp)), Regular;
+    46 => assert_ap_mul_fp_fp_appp: Assert(Ap, Mul(Fp, Fp)), Add1;
+    47 => assert_ap_mul_imm_ap: Assert(Ap, MulImm(Ap)), Regular;
+    48 => assert_ap_mul_imm_ap_appp: Assert(Ap, MulImm(Ap)), Add1;
+    49 => assert_ap_mul_imm_fp: Assert(Ap, MulImm(Fp)), Regular;
+    50 => assert_ap_mul_imm_fp_appp: Assert(Ap, MulImm(Fp)), Add1;
+    51 => assert_fp_add_ap_ap: Assert(Fp, Add(Ap, Ap)), Regular;
+    52 => assert_fp_add_ap_ap_appp: Assert(Fp, Add(Ap, Ap)), Add1;
+    53 => assert_fp_add_ap_fp: Assert(Fp, Add(Ap, Fp)), Regular;
+    54 => assert_fp_add_ap_fp_appp: Assert(Fp, Add(Ap, Fp)), Add1;
+    55 => assert_fp_add_fp_ap: Assert(Fp, Add(Fp, Ap)), Regular;
+    56 => assert_fp_add_fp_ap_appp: Assert(Fp, Add(Fp, Ap)), Add1;
+    57 => assert_fp_add_fp_fp: Assert(Fp, Add(Fp, Fp)), Regular;
+    58 => assert_fp_add_fp_fp_appp: Assert(Fp, Add(Fp, Fp)), Add1;
+    59 => assert_fp_add_imm_ap: Assert(Fp, AddImm(Ap)), Regular;
+    60 => assert_fp_add_imm_ap_appp: Assert(Fp, AddImm(Ap)), Add1;
+    61 => assert_fp_add_imm_fp: Assert(Fp, AddImm(Fp)), Regular;
+    62 => assert_fp_add_imm_fp_appp: Assert(Fp, AddImm(Fp)), Add1;
+    63 => assert_fp_deref_ap: Assert(Fp, Deref(Ap)), Regular;
+    64 => assert_fp_deref_ap_appp: Assert(Fp, Deref(Ap)), Add1;
+    65 => assert_fp_deref_fp: Assert(Fp, Deref(Fp)), Regular;
+    66 => assert_fp_deref_fp_appp: Assert(Fp, Deref(Fp)), Add1;
+    67 => assert_fp_double_deref_ap: Assert(Fp, DoubleDeref(Ap)), Regular;
+    68 => assert_fp_double_deref_ap_appp: Assert(Fp, DoubleDeref(Ap)), Add1;
+    69 => assert_fp_double_deref_fp: Assert(Fp, DoubleDeref(Fp)), Regular;
+    70 => assert_fp_double_deref_fp_appp: Assert(Fp, DoubleDeref(Fp)), Add1;
+    71 => assert_fp_imm: Assert(Fp, Imm), Regular;
+    72 => assert_fp_imm_appp: Assert(Fp, Imm), Add1;
+    73 => assert_fp_mul_ap_ap: Assert(Fp, Mul(Ap, Ap)), Regular;
+    74 => assert_fp_mul_ap_ap_appp: Assert(Fp, Mul(Ap, Ap)), Add1;
+    75 => assert_fp_mul_ap_fp: Assert(Fp, Mul(Ap, Fp)), Regular;
+    76 => assert_fp_mul_ap_fp_appp: Assert(Fp, Mul(Ap, Fp)), Add1;
+    77 => assert_fp_mul_fp_ap: Assert(Fp, Mul(Fp, Ap)), Regular;
+    78 => assert_fp_mul_fp_ap_appp: Assert(Fp, Mul(Fp, Ap)), Add1;
+    79 => assert_fp_mul_fp_fp: Assert(Fp, Mul(Fp, Fp)), Regular;
+    80 => assert_fp_mul_fp_fp_appp: Assert(Fp, Mul(Fp, Fp)), Add1;
+    81 => assert_fp_mul_imm_ap: Assert(Fp, MulImm(Ap)), Regular;
+    82 => assert_fp_mul_imm_ap_appp: Assert(Fp, MulImm(Ap)), Add1;
+    83 => assert_fp_mul_imm_fp: Assert(Fp, MulImm(Fp)), Regular;
+    84 => assert_fp_mul_imm_fp_appp: Assert(Fp, MulImm(Fp)), Add1;
+    85 => call_abs_ap: Call(Abs, Deref(Ap)), Add2;
+    86 => call_abs_fp: Call(Abs, Deref(Fp)), Add2;
+    87 => call_abs_imm: Call(Abs, Imm), Add2;
+    88 => call_rel_ap: Call(Rel, Deref(Ap)), Add2;
+    89 => call_rel_fp: Call(Rel, Deref(Fp)), Add2;
+    90 => call_rel_imm: Call(Rel, Imm), Add2;
+    91 => jmp_abs_add_ap_ap: Jmp(Abs, Add(Ap, Ap)), Regular;
+    92 => jmp_abs_add_ap_ap_appp: Jmp(Abs, Add(Ap, Ap)), Add1;
+    93 => jmp_abs_add_ap_fp: Jmp(Abs, Add(Ap, Fp)), Regular;
+    94 => jmp_abs_add_ap_fp_appp: Jmp(Abs, Add(Ap, Fp)), Add1;
+    95 => jmp_abs_add_fp_ap: Jmp(Abs, Add(Fp, Ap)), Regular;
+    96 => jmp_abs_add_fp_ap_appp: Jmp(Abs, Add(Fp, Ap)), Add1;
+    97 => jmp_abs_add_fp_fp: Jmp(Abs, Add(Fp, Fp)), Regular;
+    98 => jmp_abs_add_fp_fp_appp: Jmp(Abs, Add(Fp, Fp)), Add1;
+    99 => jmp_abs_add_imm_ap: Jmp(Abs, AddImm(Ap)), Regular;
+    100 => jmp_abs_add_imm_ap_appp: Jmp(Abs, AddImm(Ap)), Add1;
+    101 => jmp_abs_add_imm_fp: Jmp(Abs, AddImm(Fp)), Regular;
+    102 => jmp_abs_add_imm_fp_appp: Jmp(Abs, AddImm(Fp)), Add1;
+    103 => jmp_abs_deref_ap: Jmp(Abs, Deref(Ap)), Regular;
+    104 => jmp_abs_deref_ap_appp: Jmp(Abs, Deref(Ap)), Add1;
+    105 => jmp_abs_deref_fp: Jmp(Abs, Deref(Fp)), Regular;
+    106 => jmp_abs_deref_fp_appp: Jmp(Abs, Deref(Fp)), Add1;
+    107 => jmp_abs_double_deref_ap: Jmp(Abs, DoubleDeref(Ap)), Regular;
+    108 => jmp_abs_double_deref_ap_appp: Jmp(Abs, DoubleDeref(Ap)), Add1;
+    109 => jmp_abs_double_deref_fp: Jmp(Abs, DoubleDeref(Fp)), Regular;
+    110 => jmp_abs_double_deref_fp_appp: Jmp(Abs, DoubleDeref(Fp)), Add1;
+    111 => jmp_abs_imm: Jmp(Abs, Imm), Regular;
+    112 => jmp_abs_imm_appp: Jmp(Abs, Imm), Add1;
+    113 => jmp_abs_mul_ap_ap: Jmp(Abs, Mul(Ap, Ap)), Regular;
+    114 => jmp_abs_mul_ap_ap_appp: Jmp(Abs, Mul(Ap, Ap)), Add1;
+    115 => jmp_abs_mul_ap_fp: Jmp(Abs, Mul(Ap, Fp)), Regular;
+    116 => jmp_abs_mul_ap_fp_appp: Jmp(Abs, Mul(Ap, Fp)), Add1;
+    117 => jmp_abs_mul_fp_ap: Jmp(Abs, Mul(Fp, Ap)), Regular;
+    118 => jmp_abs_mul_fp_ap_appp: Jmp(Abs, Mul(Fp, Ap)), Add1;
+    119 => jmp_abs_mul_fp_fp: Jmp(Abs, Mul(Fp, Fp)), Regular;
+    120 => jmp_abs_mul_fp_fp_appp: Jmp(Abs, Mul(Fp, Fp)), Add1;
+    121 => jmp_abs_mul_imm_ap: Jmp(Abs, MulImm(Ap)), Regular;
+    122 => jmp_abs_mul_imm_ap_appp: Jmp(Abs, MulImm(Ap)), Add1;
+    123 => jmp_abs_mul_imm_fp: Jmp(Abs, MulImm(Fp)), Regular;
+    124 => jmp_abs_mul_imm_fp_appp: Jmp(Abs, MulImm(Fp)), Add1;
+    125 => jmp_rel_add_ap_ap: Jmp(Rel, Add(Ap, Ap)), Regular;
+    126 => jmp_rel_add_ap_ap_appp: Jmp(Rel, Add(Ap, Ap)), Add1;
+    127 => jmp_rel_add_ap_fp: Jmp(Rel, Add(Ap, Fp)), Regular;
+    128 => jmp_rel_add_ap_fp_appp: Jmp(Rel, Add(Ap, Fp)), Add1;
+    129 => jmp_rel_add_fp_ap: Jmp(Rel, Add(Fp, Ap)), Regular;
+    130 => jmp_rel_add_fp_ap_appp: Jmp(Rel, Add(Fp, Ap)), Add1;
+    131 => jmp_rel_add_fp_fp: Jmp(Rel, Add(Fp, Fp)), Regular;
+    132 => jmp_rel_add_fp_fp_appp: Jmp(Rel, Add(Fp, Fp)), Add1;
+    133 => jmp_rel_add_imm_ap: Jmp(Rel, AddImm(Ap)), Regular;
+    134 => jmp_rel_add_imm_ap_appp: Jmp(Rel, AddImm(Ap)), Add1;
+    135 => jmp_rel_add_imm_fp: Jmp(Rel, AddImm(Fp)), Regular;
+    136 => jmp_rel_add_imm_fp_appp: Jmp(Rel, AddImm(Fp)), Add1;
+    137 => jmp_rel_deref_ap: Jmp(Rel, Deref(Ap)), Regular;
+    138 => jmp_rel_deref_ap_appp: Jmp(Rel, Deref(Ap)), Add1;
+    139 => jmp_rel_deref_fp: Jmp(Rel, Deref(Fp)), Regular;
+    140 => jmp_rel_deref_fp_appp: Jmp(Rel, Deref(Fp)), Add1;
+    141 => jmp_rel_double_deref_ap: Jmp(Rel, DoubleDeref(Ap)), Regular;
+    142 => jmp_rel_double_deref_ap_appp: Jmp(Rel, DoubleDeref(Ap)), Add1;
+    143 => jmp_rel_double_deref_fp: Jmp(Rel, DoubleDeref(Fp)), Regular;
+    144 => jmp_rel_double_deref_fp_appp: Jmp(Rel, DoubleDeref(Fp)), Add1;
+    145 => jmp_rel_imm: Jmp(Rel, Imm), Regular;
+    146 => jmp_rel_imm_appp: Jmp(Rel, Imm), Add1;
+    147 => jmp_rel_mul_ap_ap: Jmp(Rel, Mul(Ap, Ap)), Regular;
+    148 => jmp_rel_mul_ap_ap_appp: Jmp(Rel, Mul(Ap, Ap)), Add1;
+    149 => jmp_rel_mul_ap_fp: Jmp(Rel, Mul(Ap, Fp)), Regular;
+    150 => jmp_rel_mul_ap_fp_appp: Jmp(Rel, Mul(Ap, Fp)), Add1;
+    151 => jmp_rel_mul_fp_ap: Jmp(Rel, Mul(Fp, Ap)), Regular;
+    152 => jmp_rel_mul_fp_ap_appp: Jmp(Rel, Mul(Fp, Ap)), Add1;
+    153 => jmp_rel_mul_fp_fp: Jmp(Rel, Mul(Fp, Fp)), Regular;
+    154 => jmp_rel_mul_fp_fp_appp: Jmp(Rel, Mul(Fp, Fp)), Add1;
+    155 => jmp_rel_mul_imm_ap: Jmp(Rel, MulImm(Ap)), Regular;
+    156 => jmp_rel_mul_imm_ap_appp: Jmp(Rel, MulImm(Ap)), Add1;
+    157 => jmp_rel_mul_imm_fp: Jmp(Rel, MulImm(Fp)), Regular;
+    158 => jmp_rel_mul_imm_fp_appp: Jmp(Rel, MulImm(Fp)), Add1;
+    159 => jnz_ap_ap: Jnz(Deref(Ap), Ap), Regular;
+    160 => jnz_ap_ap_appp: Jnz(Deref(Ap), Ap), Add1;
+    161 => jnz_ap_fp: Jnz(Deref(Ap), Fp), Regular;
+    162 => jnz_ap_fp_appp: Jnz(Deref(Ap), Fp), Add1;
+    163 => jnz_fp_ap: Jnz(Deref(Fp), Ap), Regular;
+    164 => jnz_fp_ap_appp: Jnz(Deref(Fp), Ap), Add1;
+    165 => jnz_fp_fp: Jnz(Deref(Fp), Fp), Regular;
+    166 => jnz_fp_fp_appp: Jnz(Deref(Fp), Fp), Add1;
+    167 => jnz_imm_ap: Jnz(Imm, Ap), Regular;
+    168 => jnz_imm_ap_appp: Jnz(Imm, Ap), Add1;
+    169 => jnz_imm_fp: Jnz(Imm, Fp), Regular;
+    170 => jnz_imm_fp_appp: Jnz(Imm, Fp), Add1;
+    171 => ret: Ret, Regular;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opcode_table() {
+        for (number, opcode) in Opcode::ALL.into_iter().enumerate() {
+            assert_eq!(Opcode::try_from(M31(number as u32)), Ok(opcode));
+            assert_eq!(M31::from(opcode), M31(number as u32));
+            // The ap update and the `_appp` suffix must agree.
+            assert_eq!(
+                opcode.ap_update() == ApUpdate::Add1,
+                opcode.name().ends_with("_appp")
+            );
+            assert_eq!(
+                Opcode::from_operation(opcode.operation(), opcode.ap_update() == ApUpdate::Add1),
+                Some(opcode)
+            );
+        }
+        assert!(Opcode::ALL
+            .windows(2)
+            .all(|pair| pair[0].name() < pair[1].name()));
+        assert_eq!(
+            Opcode::try_from(M31(172)),
+            Err(InstructionError::UnknownOpcode(M31(172)))
+        );
+    }
+
+    #[test]
+    fn test_opcode_display() {
+        assert_eq!(
+            Opcode::AssertApAddImmFpAppp.to_string(),
+            "assert_ap_add_imm_fp_appp"
+        );
+        assert_eq!(
+            Opcode::AssertApAddImmFpAppp.mnemonic(),
+            "assert_ap_add_imm_fp"
+        );
+        assert_eq!(
+            Opcode::AssertApAddImmFpAppp.operation(),
+            Operation::Assert(Register::Ap, Operand::AddImm(Register::Fp))
+        );
+    }
+}
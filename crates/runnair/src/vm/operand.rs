@@ -1,86 +1,32 @@
 use stwo_prover::core::fields::m31::M31;
 
-use super::State;
-use crate::memory::relocatable::assert_and_project;
-use crate::memory::{MaybeRelocatableValue, Memory};
-
-// Adds:
-pub(crate) fn add_ap_ap(memory: &Memory, state: State, args: &[M31]) -> MaybeRelocatableValue {
-    memory[state.ap + args[0]] + memory[state.ap + args[1]]
-}
-
-pub(crate) fn add_ap_fp(memory: &Memory, state: State, args: &[M31]) -> MaybeRelocatableValue {
-    memory[state.ap + args[0]] + memory[state.fp + args[1]]
-}
-
-pub(crate) fn add_fp_ap(memory: &Memory, state: State, args: &[M31]) -> MaybeRelocatableValue {
-    memory[state.fp + args[0]] + memory[state.ap + args[1]]
-}
-
-pub(crate) fn add_fp_fp(memory: &Memory, state: State, args: &[M31]) -> MaybeRelocatableValue {
-    memory[state.fp + args[0]] + memory[state.fp + args[1]]
-}
-
-pub(crate) fn add_imm_ap(memory: &Memory, state: State, args: &[M31]) -> MaybeRelocatableValue {
-    memory[state.ap + args[1]] + args[0]
-}
-
-pub(crate) fn add_imm_fp(memory: &Memory, state: State, args: &[M31]) -> MaybeRelocatableValue {
-    memory[state.fp + args[1]] + args[0]
-}
-
-// Muls:
-pub(crate) fn mul_ap_ap(memory: &Memory, state: State, args: &[M31]) -> MaybeRelocatableValue {
-    memory[state.ap + args[0]] * memory[state.ap + args[1]]
-}
-
-pub(crate) fn mul_ap_fp(memory: &Memory, state: State, args: &[M31]) -> MaybeRelocatableValue {
-    memory[state.ap + args[0]] * memory[state.fp + args[1]]
-}
-
-pub(crate) fn mul_fp_ap(memory: &Memory, state: State, args: &[M31]) -> MaybeRelocatableValue {
-    memory[state.fp + args[0]] * memory[state.ap + args[1]]
-}
-
-pub(crate) fn mul_fp_fp(memory: &Memory, state: State, args: &[M31]) -> MaybeRelocatableValue {
-    memory[state.fp + args[0]] * memory[state.fp + args[1]]
-}
-
-pub(crate) fn mul_imm_ap(memory: &Memory, state: State, args: &[M31]) -> MaybeRelocatableValue {
-    memory[state.ap + args[1]] * args[0]
-}
-
-pub(crate) fn mul_imm_fp(memory: &Memory, state: State, args: &[M31]) -> MaybeRelocatableValue {
-    memory[state.fp + args[1]] * args[0]
-}
-
-// Derefs:
-pub(crate) fn imm(_memory: &Memory, _state: State, args: &[M31]) -> MaybeRelocatableValue {
-    MaybeRelocatableValue::Absolute(args[0].into())
-}
-
-pub(crate) fn deref_ap(memory: &Memory, state: State, args: &[M31]) -> MaybeRelocatableValue {
-    memory[state.ap + args[0]]
-}
-
-pub(crate) fn deref_fp(memory: &Memory, state: State, args: &[M31]) -> MaybeRelocatableValue {
-    memory[state.fp + args[0]]
-}
-
-pub(crate) fn double_deref_ap(
-    memory: &Memory,
-    state: State,
-    args: &[M31],
-) -> MaybeRelocatableValue {
-    let address = assert_and_project(memory[state.ap + args[0]] + args[1]);
-    memory[address]
-}
-
-pub(crate) fn double_deref_fp(
-    memory: &Memory,
-    state: State,
-    args: &[M31],
-) -> MaybeRelocatableValue {
-    let address = assert_and_project(memory[state.fp + args[0]] + args[1]);
-    memory[address]
+use super::error::InstructionError;
+use super::opcode::{Operand, Register};
+use super::{read_memory, State};
+use crate::memory::relocatable::{TryAdd, TryMul};
+use crate::memory::{MaybeRelocatableAddr, MaybeRelocatableValue, Memory};
+
+pub(crate) type OperandResult = Result<MaybeRelocatableValue, InstructionError>;
+
+/// The value of `operand`, with the arguments layout documented on `Operand`.
+pub(crate) fn read(memory: &Memory, state: State, operand: Operand, args: &[M31]) -> OperandResult {
+    let cell =
+        |register: Register, offset: M31| read_memory(memory, state.register(register) + offset);
+
+    match operand {
+        // Adds:
+        Operand::Add(lhs, rhs) => Ok(cell(lhs, args[0])?.try_add(cell(rhs, args[1])?)?),
+        Operand::AddImm(register) => Ok(cell(register, args[1])? + args[0]),
+        // Muls:
+        Operand::Mul(lhs, rhs) => Ok(cell(lhs, args[0])?.try_mul(cell(rhs, args[1])?)?),
+        Operand::MulImm(register) => Ok(cell(register, args[1])?.try_mul(args[0])?),
+        // Derefs:
+        Operand::Imm => Ok(MaybeRelocatableValue::Absolute(args[0].into())),
+        Operand::Deref(register) => cell(register, args[0]),
+        Operand::DoubleDeref(register) => {
+            let base = cell(register, args[0])?;
+            let address = MaybeRelocatableAddr::try_from(base + args[1])?;
+            read_memory(memory, address)
+        }
+    }
 }
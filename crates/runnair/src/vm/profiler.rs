@@ -0,0 +1,184 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::memory::MaybeRelocatableAddr;
+use crate::vm::opcode::{Opcode, Operation};
+
+/// A node of the call tree: a function called along a path of calls from the entry point.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct Node {
+    /// The pc the function was called at.
+    pc: MaybeRelocatableAddr,
+    parent: Option<usize>,
+    /// The steps executed in this function itself, along this path.
+    n_steps: usize,
+}
+
+/// Counts the steps executed in each function, following `call` and `ret` with a shadow call
+/// stack.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub(crate) struct Profiler {
+    nodes: Vec<Node>,
+    children: HashMap<(usize, MaybeRelocatableAddr), usize>,
+    /// The node of the running function.
+    current: usize,
+}
+
+impl Profiler {
+    /// Creates a profiler for a run that starts at `pc`.
+    pub fn new(pc: MaybeRelocatableAddr) -> Self {
+        Self {
+            nodes: vec![Node {
+                pc,
+                parent: None,
+                n_steps: 0,
+            }],
+            children: HashMap::new(),
+            current: 0,
+        }
+    }
+
+    /// Records a step that executed `opcode` and moved to `next_pc`.
+    pub fn record(&mut self, opcode: Opcode, next_pc: MaybeRelocatableAddr) {
+        self.nodes[self.current].n_steps += 1;
+        match opcode.operation() {
+            Operation::Call(..) => {
+                let caller = self.current;
+                let next_node = self.nodes.len();
+                self.current = *self.children.entry((caller, next_pc)).or_insert(next_node);
+                if self.current == next_node {
+                    self.nodes.push(Node {
+                        pc: next_pc,
+                        parent: Some(caller),
+                        n_steps: 0,
+                    });
+                }
+            }
+            Operation::Ret => {
+                // The entry point returns to the end of the run, with no caller to go back to.
+                self.current = self.nodes[self.current].parent.unwrap_or(self.current);
+            }
+            _ => {}
+        }
+    }
+
+    /// The profile of the steps recorded so far, naming each function with `name`.
+    pub fn profile(&self, name: impl Fn(MaybeRelocatableAddr) -> String) -> Profile {
+        let mut stacks = vec![];
+        let mut functions = HashMap::<String, FunctionProfile>::new();
+        for (index, node) in self.nodes.iter().enumerate() {
+            let stack: Vec<String> = self.path(index).map(&name).collect();
+            let leaf = &stack[stack.len() - 1];
+            function_entry(&mut functions, leaf).exclusive_steps += node.n_steps;
+            // Recursive functions appear several times in a stack, but count once per step.
+            for function in stack.iter().collect::<HashSet<_>>() {
+                function_entry(&mut functions, function).inclusive_steps += node.n_steps;
+            }
+            if node.n_steps > 0 {
+                stacks.push((stack, node.n_steps));
+            }
+        }
+
+        let mut functions: Vec<_> = functions.into_values().collect();
+        functions.sort_by(|a, b| (b.inclusive_steps, &a.name).cmp(&(a.inclusive_steps, &b.name)));
+        stacks.sort();
+
+        Profile { functions, stacks }
+    }
+
+    /// The pcs of the functions from the entry point to `node`.
+    fn path(&self, node: usize) -> impl Iterator<Item = MaybeRelocatableAddr> {
+        let mut pcs = vec![];
+        let mut node = Some(node);
+        while let Some(index) = node {
+            pcs.push(self.nodes[index].pc);
+            node = self.nodes[index].parent;
+        }
+        pcs.into_iter().rev()
+    }
+}
+
+fn function_entry<'a>(
+    functions: &'a mut HashMap<String, FunctionProfile>,
+    name: &str,
+) -> &'a mut FunctionProfile {
+    functions
+        .entry(name.to_string())
+        .or_insert_with(|| FunctionProfile {
+            name: name.to_string(),
+            inclusive_steps: 0,
+            exclusive_steps: 0,
+        })
+}
+
+/// The steps spent in each function of a run.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Profile {
+    /// The functions, by decreasing inclusive steps.
+    pub functions: Vec<FunctionProfile>,
+    /// The steps executed with each call stack, outermost function first.
+    pub stacks: Vec<(Vec<String>, usize)>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FunctionProfile {
+    pub name: String,
+    /// The steps executed in the function and the functions it called.
+    pub inclusive_steps: usize,
+    /// The steps executed in the function itself.
+    pub exclusive_steps: usize,
+}
+
+impl Profile {
+    /// Writes the call stacks in the folded format read by flamegraph tools: one `a;b;c steps`
+    /// line per stack.
+    pub fn write_folded(&self, mut writer: impl Write) -> io::Result<()> {
+        for (stack, n_steps) in &self.stacks {
+            writeln!(writer, "{} {n_steps}", stack.join(";"))?;
+        }
+        writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::fibonacci_vm;
+
+    #[test]
+    fn test_fibonacci_profile() {
+        let mut vm = fibonacci_vm(5);
+        vm.enable_profiling();
+        vm.execute().unwrap();
+
+        let profile = vm.profile().unwrap();
+
+        let main = &profile.functions[0];
+        assert_eq!(main.name, "__main__.main");
+        assert_eq!(main.inclusive_steps, vm.n_steps());
+        let fib = &profile.functions[1];
+        assert_eq!(fib.name, "__main__.fib");
+        assert_eq!(main.exclusive_steps + fib.inclusive_steps, vm.n_steps());
+        assert_eq!(fib.exclusive_steps, fib.inclusive_steps);
+
+        // `main` calls `fib`, which calls itself 5 times.
+        let deepest = profile.stacks.iter().map(|(stack, _)| stack.len()).max();
+        assert_eq!(deepest, Some(7));
+        assert_eq!(
+            profile
+                .stacks
+                .iter()
+                .map(|(_, n_steps)| n_steps)
+                .sum::<usize>(),
+            vm.n_steps()
+        );
+
+        let mut folded = vec![];
+        profile.write_folded(&mut folded).unwrap();
+        let folded = String::from_utf8(folded).unwrap();
+        assert_eq!(folded.lines().count(), profile.stacks.len());
+        assert!(folded.starts_with("__main__.main "));
+        assert!(folded.contains("\n__main__.main;__main__.fib;__main__.fib "));
+    }
+}
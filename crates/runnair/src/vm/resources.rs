@@ -0,0 +1,69 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::memory::segments::SegmentKind;
+
+/// The resources used by a run, to estimate its proving cost.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ExecutionResources {
+    pub n_steps: usize,
+    /// The number of executed instructions of each opcode, by opcode name. Opcodes that were never
+    /// executed are omitted.
+    pub opcode_counts: BTreeMap<&'static str, usize>,
+    /// The memory used by each segment, by segment index.
+    pub segments: Vec<SegmentResources>,
+    /// The highest offset of `ap` in the execution segment.
+    pub max_ap: usize,
+    /// The number of cells used by each builtin, by builtin name.
+    pub builtins: BTreeMap<&'static str, usize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct SegmentResources {
+    pub kind: SegmentKind,
+    /// The number of cells up to and including the last written cell, or the finalized size.
+    pub size: usize,
+    /// The number of written cells.
+    pub n_cells: usize,
+    /// The number of cells in `size` that were never written.
+    pub n_holes: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{fibonacci_program, fibonacci_vm};
+
+    #[test]
+    fn test_fibonacci_resources() {
+        let n_instructions = fibonacci_program().instructions.len();
+        let mut vm = fibonacci_vm(5);
+        vm.execute().unwrap();
+
+        let resources = vm.execution_resources();
+
+        assert_eq!(resources.n_steps, vm.n_steps());
+        assert_eq!(
+            resources.opcode_counts.values().sum::<usize>(),
+            resources.n_steps
+        );
+        // `main` calls `fib`, which calls itself 5 times; `main` returns too.
+        assert_eq!(resources.opcode_counts["call_rel_imm"], 6);
+        assert_eq!(resources.opcode_counts["ret"], 7);
+        assert_eq!(
+            resources.segments[0],
+            SegmentResources {
+                kind: SegmentKind::Program,
+                size: n_instructions,
+                n_cells: n_instructions,
+                n_holes: 0,
+            }
+        );
+        assert_eq!(resources.builtins["output"], 2);
+        assert_eq!(resources.max_ap, resources.segments[1].size);
+
+        let json = serde_json::to_value(&resources).unwrap();
+        assert_eq!(json["segments"][2]["kind"], "output");
+    }
+}
@@ -0,0 +1,262 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::{BufReader, BufWriter, Read, Write};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::memory::relocatable::{Relocatable, RelocationTable, Segment};
+use crate::memory::Memory;
+use crate::vm::builtins::Builtin;
+use crate::vm::debug_info::InstructionLocation;
+use crate::vm::decoded_program::DecodedProgram;
+use crate::vm::error::ProgramError;
+use crate::vm::hints::HintRunner;
+use crate::vm::input::Input;
+use crate::vm::loop_detection::LoopDetector;
+use crate::vm::profiler::Profiler;
+use crate::vm::trace::TraceEntry;
+use crate::vm::{State, VM};
+
+/// The version of the snapshot format, to bump on incompatible changes.
+const SNAPSHOT_VERSION: u32 = 4;
+
+#[derive(Debug, Error)]
+pub enum SnapshotError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to write the snapshot: {0}")]
+    Encode(#[from] bincode::error::EncodeError),
+    #[error("Failed to read the snapshot: {0}")]
+    Decode(#[from] bincode::error::DecodeError),
+    #[error("Unsupported snapshot version {0}; expected {SNAPSHOT_VERSION}.")]
+    UnsupportedVersion(u32),
+}
+
+/// The state of a run, to save it and resume it later with `VM::restore`.
+///
+/// Holds everything in the VM but the hint processors, which are code and are registered again on
+/// restore, and the decoded program, which is rebuilt from memory. The memory and the traces are
+/// borrowed from the VM, so taking a snapshot to write it does not copy them.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Snapshot<'a> {
+    memory: Cow<'a, Memory>,
+    program_segment: Segment,
+    state: State,
+    #[serde(with = "input_json")]
+    input: Input,
+    hints: HashMap<Relocatable, Vec<String>>,
+    trace: Option<Cow<'a, [State]>>,
+    relocated_trace: Option<Cow<'a, [TraceEntry]>>,
+    relocation_table: Option<RelocationTable>,
+    n_steps: usize,
+    max_steps: Option<usize>,
+    loop_detector: Option<LoopDetector>,
+    builtin_bases: Vec<(Builtin, Relocatable)>,
+    return_size: usize,
+    final_fp: Relocatable,
+    final_pc: Relocatable,
+    proof_mode: bool,
+    instruction_locations: HashMap<usize, InstructionLocation>,
+    function_names: HashMap<usize, String>,
+    profiler: Option<Profiler>,
+    opcode_counts: Vec<usize>,
+    max_ap: u32,
+}
+
+impl Snapshot<'_> {
+    /// The number of instructions executed before the snapshot was taken.
+    pub fn n_steps(&self) -> usize {
+        self.n_steps
+    }
+
+    /// Writes the snapshot in a compact binary format.
+    pub fn write(&self, writer: impl Write) -> Result<(), SnapshotError> {
+        let mut writer = BufWriter::new(writer);
+        let config = bincode::config::standard();
+        bincode::serde::encode_into_std_write(SNAPSHOT_VERSION, &mut writer, config)?;
+        bincode::serde::encode_into_std_write(self, &mut writer, config)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Reads a snapshot written by `write`.
+    pub fn read(reader: impl Read) -> Result<Snapshot<'static>, SnapshotError> {
+        let mut reader = BufReader::new(reader);
+        let config = bincode::config::standard();
+        let version: u32 = bincode::serde::decode_from_std_read(&mut reader, config)?;
+        if version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+
+        Ok(bincode::serde::decode_from_std_read(&mut reader, config)?)
+    }
+}
+
+impl VM {
+    /// Captures the state of the run, to resume it later with `restore`.
+    pub fn snapshot(&self) -> Snapshot<'_> {
+        Snapshot {
+            memory: Cow::Borrowed(&self.memory),
+            program_segment: self.decoded_program.segment(),
+            state: self.state,
+            input: self.hint_runner.input().clone(),
+            hints: self.hint_runner.hints().clone(),
+            trace: self.trace.as_deref().map(Cow::Borrowed),
+            relocated_trace: self.relocated_trace.as_deref().map(Cow::Borrowed),
+            relocation_table: self.relocation_table.clone(),
+            n_steps: self.n_steps,
+            max_steps: self.max_steps,
+            loop_detector: self.loop_detector.clone(),
+            builtin_bases: self.builtin_bases.clone(),
+            return_size: self.return_size,
+            final_fp: self.final_fp,
+            final_pc: self.final_pc,
+            proof_mode: self.proof_mode,
+            instruction_locations: self.instruction_locations.clone(),
+            function_names: self.function_names.clone(),
+            profiler: self.profiler.clone(),
+            opcode_counts: self.opcode_counts.clone(),
+            max_ap: self.max_ap,
+        }
+    }
+
+    /// Resumes the run captured in `snapshot`, with the hints run by the processors registered in
+    /// `hint_runner`. The input and the hints of the program are restored from the snapshot, and
+    /// replace any already loaded in `hint_runner`.
+    pub fn restore(
+        snapshot: Snapshot<'_>,
+        mut hint_runner: HintRunner,
+    ) -> Result<Self, ProgramError> {
+        let memory = snapshot.memory.into_owned();
+        let decoded_program = DecodedProgram::new(&memory, snapshot.program_segment);
+        hint_runner.set_input(snapshot.input);
+        hint_runner.clear_hints();
+        for (pc, codes) in snapshot.hints {
+            hint_runner.add_hints(pc, codes)?;
+        }
+
+        Ok(Self {
+            memory,
+            state: snapshot.state,
+            hint_runner,
+            trace: snapshot.trace.map(Cow::into_owned),
+            relocated_trace: snapshot.relocated_trace.map(Cow::into_owned),
+            relocation_table: snapshot.relocation_table,
+            n_steps: snapshot.n_steps,
+            max_steps: snapshot.max_steps,
+            loop_detector: snapshot.loop_detector,
+            builtin_bases: snapshot.builtin_bases,
+            return_size: snapshot.return_size,
+            final_fp: snapshot.final_fp,
+            final_pc: snapshot.final_pc,
+            proof_mode: snapshot.proof_mode,
+            instruction_locations: snapshot.instruction_locations,
+            function_names: snapshot.function_names,
+            profiler: snapshot.profiler,
+            decoded_program,
+            opcode_counts: snapshot.opcode_counts,
+            max_ap: snapshot.max_ap,
+        })
+    }
+}
+
+/// Stores the input as its JSON text, since reading it back needs a self-describing format.
+mod input_json {
+    use serde::de::Error as _;
+    use serde::ser::Error as _;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::vm::input::Input;
+
+    pub fn serialize<S: Serializer>(input: &Input, serializer: S) -> Result<S::Ok, S::Error> {
+        serde_json::to_string(input)
+            .map_err(S::Error::custom)?
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Input, D::Error> {
+        let json = String::deserialize(deserializer)?;
+        serde_json::from_str(&json).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{fibonacci_input, fibonacci_program};
+
+    fn proof_mode_fibonacci_vm() -> VM {
+        let mut vm = VM::create_for_proof_mode(
+            fibonacci_program(),
+            "__main__.main",
+            HintRunner::new(fibonacci_input(5)),
+        )
+        .unwrap();
+        vm.enable_trace_recording();
+        vm.enable_loop_detection();
+
+        vm
+    }
+
+    #[test]
+    fn test_snapshot_and_restore() {
+        let mut expected = proof_mode_fibonacci_vm();
+        expected.execute().unwrap();
+        let expected_output = expected.output().unwrap();
+        let expected_resources = expected.execution_resources();
+        expected.relocate();
+
+        // Pause before the hint that reads the input, which must come from the snapshot.
+        let mut vm = proof_mode_fibonacci_vm();
+        assert!(!vm.execute_until(2).unwrap());
+        let mut bytes = vec![];
+        vm.snapshot().write(&mut bytes).unwrap();
+        drop(vm);
+        let snapshot = Snapshot::read(bytes.as_slice()).unwrap();
+        assert_eq!(snapshot.n_steps(), 2);
+        let mut vm = VM::restore(snapshot, HintRunner::new(Input::default())).unwrap();
+
+        assert!(vm.execute_until(usize::MAX).unwrap());
+
+        assert_eq!(vm.n_steps(), expected.n_steps());
+        assert_eq!(vm.state(), expected.state());
+        assert_eq!(vm.output().unwrap(), expected_output);
+        assert_eq!(vm.execution_resources(), expected_resources);
+        vm.relocate();
+        assert_eq!(vm.relocated_trace(), expected.relocated_trace());
+        assert_eq!(
+            vm.memory().absolute_data(),
+            expected.memory().absolute_data()
+        );
+    }
+
+    #[test]
+    fn test_restore_replaces_hints() {
+        let vm = proof_mode_fibonacci_vm();
+        let snapshot = vm.snapshot();
+        let hints = snapshot.hints.clone();
+        let mut hint_runner = HintRunner::new(Input::default());
+        for (&pc, codes) in &hints {
+            hint_runner.add_hints(pc, codes.clone()).unwrap();
+        }
+
+        let vm = VM::restore(snapshot, hint_runner).unwrap();
+
+        assert_eq!(vm.hint_runner.hints(), &hints);
+    }
+
+    #[test]
+    fn test_unsupported_version() {
+        let mut bytes = vec![];
+        let config = bincode::config::standard();
+        bincode::serde::encode_into_std_write(SNAPSHOT_VERSION + 1, &mut bytes, config).unwrap();
+
+        let error = Snapshot::read(bytes.as_slice()).unwrap_err();
+
+        assert!(
+            matches!(error, SnapshotError::UnsupportedVersion(version) if version == SNAPSHOT_VERSION + 1)
+        );
+    }
+}
@@ -0,0 +1,760 @@
+//! An executable reference specification of the opcodes, decoded from their numbers independently
+//! of the opcode table, and checked against the handlers on random states and memories and on
+//! hand-written instructions.
+
+use std::collections::HashMap;
+
+use num_traits::Zero;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use stwo_prover::core::fields::m31::{M31, P};
+use stwo_prover::core::fields::qm31::QM31;
+
+use super::decoded_program::DecodedInstruction;
+use super::opcode::{JumpMode, Opcode, Operand, Operation, Register};
+use super::{Instruction, InstructionArgs, State};
+use crate::memory::relocatable::{MaybeRelocatable, Relocatable};
+use crate::memory::segments::SegmentKind;
+use crate::memory::{MaybeRelocatableAddr, MaybeRelocatableValue, Memory};
+use crate::utils::usize_from_u32;
+
+const PROGRAM: usize = 0;
+const EXECUTION: usize = 1;
+const DATA: usize = 2;
+
+/// The cells of the tests are at offsets below this bound, in the segments above and at absolute
+/// addresses.
+const N_CELLS: u32 = 32;
+
+const N_CASES: usize = 50;
+
+/// The operands in the order of their names.
+const OPERANDS: [Operand; 17] = {
+    use Operand::*;
+    use Register::*;
+
+    [
+        Add(Ap, Ap),
+        Add(Ap, Fp),
+        Add(Fp, Ap),
+        Add(Fp, Fp),
+        AddImm(Ap),
+        AddImm(Fp),
+        Deref(Ap),
+        Deref(Fp),
+        DoubleDeref(Ap),
+        DoubleDeref(Fp),
+        Imm,
+        Mul(Ap, Ap),
+        Mul(Ap, Fp),
+        Mul(Fp, Ap),
+        Mul(Fp, Fp),
+        MulImm(Ap),
+        MulImm(Fp),
+    ]
+};
+
+/// The operation of the opcode `number` and whether it increments `ap`, decoded from the number
+/// alone rather than looked up in the opcode table. Opcodes are numbered in the order of their
+/// names, e.g. `addap_*` first, and each `_appp` opcode right after the one it increments `ap` in.
+fn decode(number: u32) -> Option<(Operation, bool)> {
+    use JumpMode::*;
+    use Operand::*;
+    use Operation::*;
+    use Register::*;
+
+    // Past `addap_*`, the `_appp` opcodes are the even ones, and pairs share their operand.
+    let increments_ap = number % 2 == 0;
+    let paired_operand = |first: u32| OPERANDS[usize_from_u32((number - first) / 2)];
+    Some(match number {
+        0..=16 => (AddAp(OPERANDS[usize_from_u32(number)]), false),
+        17..=50 => (Assert(Ap, paired_operand(17)), increments_ap),
+        51..=84 => (Assert(Fp, paired_operand(51)), increments_ap),
+        85..=90 => {
+            let mode = if number < 88 { Abs } else { Rel };
+            let target = [Deref(Ap), Deref(Fp), Imm][usize_from_u32((number - 85) % 3)];
+            (Call(mode, target), false)
+        }
+        91..=124 => (Jmp(Abs, paired_operand(91)), increments_ap),
+        125..=158 => (Jmp(Rel, paired_operand(125)), increments_ap),
+        159..=170 => {
+            let pair = usize_from_u32((number - 159) / 2);
+            let target = [Deref(Ap), Deref(Fp), Imm][pair / 2];
+            let condition = [Ap, Fp][pair % 2];
+            (Jnz(target, condition), increments_ap)
+        }
+        171 => (Ret, false),
+        _ => return None,
+    })
+}
+
+/// Why the specification rejects an instruction.
+#[derive(Debug)]
+enum Fault {
+    /// The instruction is invalid, and the handler must fail.
+    Invalid,
+    /// The behavior is left to the implementation, e.g. a write far from the cells of the test, so
+    /// the case is skipped.
+    Unspecified,
+}
+
+type SpecResult<T> = Result<T, Fault>;
+
+type Cells = HashMap<MaybeRelocatableAddr, MaybeRelocatableValue>;
+
+/// A write-once memory that records the cells an instruction accesses.
+struct SpecMemory {
+    cells: Cells,
+    accessed: Vec<MaybeRelocatableAddr>,
+}
+
+impl SpecMemory {
+    fn peek(&mut self, address: MaybeRelocatableAddr) -> Option<MaybeRelocatableValue> {
+        self.accessed.push(address);
+        self.cells.get(&address).copied()
+    }
+
+    fn read(&mut self, address: MaybeRelocatableAddr) -> SpecResult<MaybeRelocatableValue> {
+        self.peek(address).ok_or(Fault::Invalid)
+    }
+
+    /// Writes an unknown cell, or checks that a known one holds `value`.
+    fn assign(
+        &mut self,
+        address: MaybeRelocatableAddr,
+        value: MaybeRelocatableValue,
+    ) -> SpecResult<()> {
+        if !in_bounds(address) {
+            return Err(Fault::Unspecified);
+        }
+        match self.peek(address) {
+            Some(known) if known != value => Err(Fault::Invalid),
+            _ => {
+                self.cells.insert(address, value);
+                Ok(())
+            }
+        }
+    }
+}
+
+fn in_bounds(address: MaybeRelocatableAddr) -> bool {
+    match address {
+        MaybeRelocatable::Relocatable(address) => address.offset.0 < N_CELLS,
+        MaybeRelocatable::Absolute(address) => address.0 < N_CELLS,
+    }
+}
+
+fn offset(address: MaybeRelocatableAddr, offset: M31) -> MaybeRelocatableAddr {
+    match address {
+        MaybeRelocatable::Relocatable(Relocatable {
+            segment,
+            offset: base,
+        }) => MaybeRelocatable::Relocatable(Relocatable {
+            segment,
+            offset: base + offset,
+        }),
+        MaybeRelocatable::Absolute(base) => MaybeRelocatable::Absolute(base + offset),
+    }
+}
+
+fn cell(state: State, register: Register, arg: M31) -> MaybeRelocatableAddr {
+    let base = match register {
+        Register::Ap => state.ap,
+        Register::Fp => state.fp,
+    };
+    offset(base, arg)
+}
+
+/// The projection of a value in the base field.
+fn base_field(value: QM31) -> SpecResult<M31> {
+    match value.to_m31_array() {
+        [value, b, c, d] if b.is_zero() && c.is_zero() && d.is_zero() => Ok(value),
+        _ => Err(Fault::Invalid),
+    }
+}
+
+fn address(value: MaybeRelocatableValue) -> SpecResult<MaybeRelocatableAddr> {
+    Ok(match value {
+        MaybeRelocatable::Relocatable(address) => MaybeRelocatable::Relocatable(address),
+        MaybeRelocatable::Absolute(value) => MaybeRelocatable::Absolute(base_field(value)?),
+    })
+}
+
+fn absolute(value: M31) -> MaybeRelocatableValue {
+    MaybeRelocatable::Absolute(value.into())
+}
+
+#[derive(Clone, Copy)]
+enum BinaryOp {
+    Add,
+    Mul,
+}
+
+impl BinaryOp {
+    fn apply(
+        self,
+        lhs: MaybeRelocatableValue,
+        rhs: MaybeRelocatableValue,
+    ) -> SpecResult<MaybeRelocatableValue> {
+        use MaybeRelocatable::*;
+
+        match (self, lhs, rhs) {
+            (Self::Add, Absolute(lhs), Absolute(rhs)) => Ok(Absolute(lhs + rhs)),
+            (Self::Add, Relocatable(address), Absolute(value))
+            | (Self::Add, Absolute(value), Relocatable(address)) => {
+                Ok(offset(Relocatable(address), base_field(value)?).into())
+            }
+            (Self::Mul, Absolute(lhs), Absolute(rhs)) => Ok(Absolute(lhs * rhs)),
+            _ => Err(Fault::Invalid),
+        }
+    }
+
+    /// Solves `result = x op known` for `x`.
+    fn solve(
+        self,
+        result: MaybeRelocatableValue,
+        known: MaybeRelocatableValue,
+    ) -> SpecResult<MaybeRelocatableValue> {
+        use MaybeRelocatable::*;
+
+        match (self, result, known) {
+            (Self::Add, Absolute(result), Absolute(known)) => Ok(Absolute(result - known)),
+            (Self::Add, Relocatable(address), Absolute(known)) => {
+                Ok(offset(Relocatable(address), -base_field(known)?).into())
+            }
+            (Self::Add, Relocatable(result), Relocatable(known))
+                if result.segment == known.segment =>
+            {
+                Ok(absolute(result.offset - known.offset))
+            }
+            // A product with zero loses the other operand, so it can't be deduced.
+            (Self::Mul, Absolute(_), Absolute(known)) if known.is_zero() => Err(Fault::Invalid),
+            (Self::Mul, Absolute(result), Absolute(known)) => Ok(Absolute(result / known)),
+            _ => Err(Fault::Invalid),
+        }
+    }
+}
+
+/// A term of an assertion: a memory cell or an immediate.
+#[derive(Clone, Copy)]
+enum Term {
+    Cell(MaybeRelocatableAddr),
+    Imm(M31),
+}
+
+impl Term {
+    fn value(self, memory: &mut SpecMemory) -> Option<MaybeRelocatableValue> {
+        match self {
+            Self::Cell(address) => memory.peek(address),
+            Self::Imm(value) => Some(absolute(value)),
+        }
+    }
+
+    fn assign(self, memory: &mut SpecMemory, value: MaybeRelocatableValue) -> SpecResult<()> {
+        match self {
+            Self::Cell(address) => memory.assign(address, value),
+            Self::Imm(_) => unreachable!("Immediates are always known."),
+        }
+    }
+}
+
+/// `dest = lhs op rhs`, deducing whichever of the three cells is unknown.
+fn assert_binary(
+    memory: &mut SpecMemory,
+    op: BinaryOp,
+    dest: MaybeRelocatableAddr,
+    lhs: Term,
+    rhs: Term,
+) -> SpecResult<()> {
+    match (memory.peek(dest), lhs.value(memory), rhs.value(memory)) {
+        (_, Some(lhs), Some(rhs)) => memory.assign(dest, op.apply(lhs, rhs)?),
+        (Some(dest), None, Some(rhs)) => lhs.assign(memory, op.solve(dest, rhs)?),
+        (Some(dest), Some(lhs), None) => rhs.assign(memory, op.solve(dest, lhs)?),
+        _ => Err(Fault::Invalid),
+    }
+}
+
+/// `dest = [src]`, copying whichever of the two cells is known to the other.
+fn assert_copy(
+    memory: &mut SpecMemory,
+    dest: MaybeRelocatableAddr,
+    src: MaybeRelocatableAddr,
+) -> SpecResult<()> {
+    match (memory.peek(dest), memory.peek(src)) {
+        (_, Some(value)) => memory.assign(dest, value),
+        (Some(value), None) => memory.assign(src, value),
+        (None, None) => Err(Fault::Invalid),
+    }
+}
+
+fn assert(
+    memory: &mut SpecMemory,
+    state: State,
+    dest: Register,
+    operand: Operand,
+    args: InstructionArgs,
+) -> SpecResult<()> {
+    let dest = cell(state, dest, args[0]);
+    match operand {
+        Operand::Imm => memory.assign(dest, absolute(args[1])),
+        Operand::Deref(src) => assert_copy(memory, dest, cell(state, src, args[1])),
+        Operand::DoubleDeref(src) => {
+            let base = memory.read(cell(state, src, args[1]))?;
+            let src = address(BinaryOp::Add.apply(base, absolute(args[2]))?)?;
+            assert_copy(memory, dest, src)
+        }
+        Operand::Add(lhs, rhs) | Operand::Mul(lhs, rhs) => {
+            let op = match operand {
+                Operand::Add(..) => BinaryOp::Add,
+                _ => BinaryOp::Mul,
+            };
+            let lhs = Term::Cell(cell(state, lhs, args[1]));
+            let rhs = Term::Cell(cell(state, rhs, args[2]));
+            assert_binary(memory, op, dest, lhs, rhs)
+        }
+        Operand::AddImm(src) | Operand::MulImm(src) => {
+            let op = match operand {
+                Operand::AddImm(_) => BinaryOp::Add,
+                _ => BinaryOp::Mul,
+            };
+            let src = Term::Cell(cell(state, src, args[2]));
+            assert_binary(memory, op, dest, src, Term::Imm(args[1]))
+        }
+    }
+}
+
+/// The value of an operand, with its arguments starting at `args[0]`.
+fn operand(
+    memory: &mut SpecMemory,
+    state: State,
+    operand: Operand,
+    args: &[M31],
+) -> SpecResult<MaybeRelocatableValue> {
+    match operand {
+        Operand::Imm => Ok(absolute(args[0])),
+        Operand::Deref(base) => memory.read(cell(state, base, args[0])),
+        Operand::DoubleDeref(base) => {
+            let base = memory.read(cell(state, base, args[0]))?;
+            let address = address(BinaryOp::Add.apply(base, absolute(args[1]))?)?;
+            memory.read(address)
+        }
+        Operand::Add(lhs, rhs) | Operand::Mul(lhs, rhs) => {
+            let op = match operand {
+                Operand::Add(..) => BinaryOp::Add,
+                _ => BinaryOp::Mul,
+            };
+            let lhs = memory.read(cell(state, lhs, args[0]))?;
+            let rhs = memory.read(cell(state, rhs, args[1]))?;
+            op.apply(lhs, rhs)
+        }
+        Operand::AddImm(base) | Operand::MulImm(base) => {
+            let op = match operand {
+                Operand::AddImm(_) => BinaryOp::Add,
+                _ => BinaryOp::Mul,
+            };
+            op.apply(memory.read(cell(state, base, args[1]))?, absolute(args[0]))
+        }
+    }
+}
+
+/// `pc` after a jump to `target`.
+fn jump(state: State, mode: JumpMode, target: MaybeRelocatableValue) -> SpecResult<State> {
+    let target = address(target)?;
+    let pc = match mode {
+        JumpMode::Abs => target,
+        JumpMode::Rel => address(BinaryOp::Add.apply(state.pc.into(), target.into())?)?,
+    };
+
+    Ok(State { pc, ..state })
+}
+
+/// Executes an instruction on `memory` as specified by the decoded opcode number.
+fn execute(
+    (operation, increments_ap): (Operation, bool),
+    memory: &mut SpecMemory,
+    state: State,
+    args: InstructionArgs,
+) -> SpecResult<State> {
+    let next_pc = offset(state.pc, M31(1));
+    let next = match operation {
+        Operation::AddAp(summand) => {
+            let summand = address(operand(memory, state, summand, &args)?)?;
+            let ap = address(BinaryOp::Add.apply(state.ap.into(), summand.into())?)?;
+            State {
+                ap,
+                pc: next_pc,
+                ..state
+            }
+        }
+        Operation::Assert(dest, src) => {
+            assert(memory, state, dest, src, args)?;
+            State {
+                pc: next_pc,
+                ..state
+            }
+        }
+        Operation::Call(mode, target) => {
+            memory.assign(state.ap, state.fp.into())?;
+            memory.assign(offset(state.ap, M31(1)), next_pc.into())?;
+            let target = operand(memory, state, target, &args)?;
+            let frame = offset(state.ap, M31(2));
+            jump(
+                State {
+                    ap: frame,
+                    fp: frame,
+                    ..state
+                },
+                mode,
+                target,
+            )?
+        }
+        Operation::Jmp(mode, target) => {
+            let target = operand(memory, state, target, &args)?;
+            jump(state, mode, target)?
+        }
+        Operation::Jnz(target, condition) => {
+            let target = operand(memory, state, target, &args)?;
+            let MaybeRelocatable::Absolute(condition) =
+                memory.read(cell(state, condition, args[1]))?
+            else {
+                return Err(Fault::Invalid);
+            };
+            if condition.is_zero() {
+                address(target)?;
+                State {
+                    pc: next_pc,
+                    ..state
+                }
+            } else {
+                jump(state, JumpMode::Rel, target)?
+            }
+        }
+        Operation::Ret => State {
+            fp: address(memory.read(offset(state.fp, -M31(2)))?)?,
+            pc: address(memory.read(offset(state.fp, -M31(1)))?)?,
+            ..state
+        },
+    };
+
+    Ok(if increments_ap {
+        State {
+            ap: offset(next.ap, M31(1)),
+            ..next
+        }
+    } else {
+        next
+    })
+}
+
+fn random_value(rng: &mut StdRng) -> MaybeRelocatableValue {
+    match rng.gen_range(0..8) {
+        0 => absolute(M31(0)),
+        // Small enough to be an address or an offset in the cells of the test.
+        1..=3 => absolute(M31(rng.gen_range(4..16))),
+        4 => absolute(M31(rng.gen_range(0..P))),
+        5 => MaybeRelocatable::Absolute(QM31::from_m31_array(std::array::from_fn(|_| {
+            M31(rng.gen_range(0..P))
+        }))),
+        6 => MaybeRelocatable::Relocatable((EXECUTION, rng.gen_range(4..16)).into()),
+        _ => MaybeRelocatable::Relocatable((DATA, rng.gen_range(4..16)).into()),
+    }
+}
+
+/// A memory with about three quarters of the cells of the test known.
+fn random_cells(rng: &mut StdRng) -> Cells {
+    let mut cells = Cells::new();
+    for address in addresses() {
+        if rng.gen_bool(0.75) {
+            cells.insert(address, random_value(rng));
+        }
+    }
+
+    cells
+}
+
+fn random_state(rng: &mut StdRng) -> State {
+    State {
+        ap: MaybeRelocatable::Relocatable((EXECUTION, rng.gen_range(8..24)).into()),
+        fp: MaybeRelocatable::Relocatable((EXECUTION, rng.gen_range(8..24)).into()),
+        pc: MaybeRelocatable::Relocatable((PROGRAM, rng.gen_range(0..16)).into()),
+    }
+}
+
+/// Offsets and immediates alike are in `[-4, 4)`, to keep every write in the cells of the test.
+fn random_args(rng: &mut StdRng) -> InstructionArgs {
+    std::array::from_fn(|_| M31(rng.gen_range(0..8)) - M31(4))
+}
+
+/// Runs the handler of `opcode` and checks it against the specification. Returns the cells after a
+/// valid instruction, or else the last cell that an invalid one accessed.
+fn check(
+    opcode: Opcode,
+    state: State,
+    args: InstructionArgs,
+    cells: &Cells,
+) -> Result<SpecMemory, Option<MaybeRelocatableAddr>> {
+    let mut expected_memory = SpecMemory {
+        cells: cells.clone(),
+        accessed: vec![],
+    };
+    let spec = decode(opcode as u32).unwrap();
+    let expected = execute(spec, &mut expected_memory, state, args);
+    if matches!(expected, Err(Fault::Unspecified)) {
+        return Err(None);
+    }
+    let mut memory = memory(cells);
+    let actual = opcode.handler()(&mut memory, state, args);
+
+    let Ok(expected) = expected else {
+        assert!(
+            actual.is_err(),
+            "{opcode} with {args:?} at {state:?} on {cells:?}: expected an error, got {actual:?}."
+        );
+        return Err(expected_memory.accessed.last().copied());
+    };
+    assert_eq!(
+        actual,
+        Ok(expected),
+        "{opcode} with {args:?} at {state:?} on {cells:?}."
+    );
+    for address in addresses() {
+        assert_eq!(
+            memory.get(address),
+            expected_memory.cells.get(&address).copied(),
+            "{opcode} with {args:?} at {state:?} on {cells:?}: at {address:?}."
+        );
+    }
+
+    Ok(expected_memory)
+}
+
+/// A memory with the segments of the test, holding `cells`.
+fn memory(cells: &Cells) -> Memory {
+    let mut memory = Memory::default();
+    for kind in [
+        SegmentKind::Program,
+        SegmentKind::Execution,
+        SegmentKind::Input,
+    ] {
+        memory.add_segment(kind);
+    }
+    memory
+        .insert_all(cells.iter().map(|(&address, &value)| (address, value)))
+        .unwrap();
+
+    memory
+}
+
+/// The addresses of the cells of the test.
+fn addresses() -> impl Iterator<Item = MaybeRelocatableAddr> {
+    [PROGRAM, EXECUTION, DATA]
+        .into_iter()
+        .flat_map(|segment| {
+            (0..N_CELLS).map(move |offset| MaybeRelocatable::Relocatable((segment, offset).into()))
+        })
+        .chain((0..N_CELLS).map(|address| MaybeRelocatable::Absolute(M31(address))))
+}
+
+#[test]
+fn test_opcodes_against_spec() {
+    let mut rng = StdRng::seed_from_u64(0);
+
+    // A byte covers every opcode number and some invalid ones.
+    for number in 0..256 {
+        if decode(number).is_none() {
+            assert!(Opcode::try_from(M31(number)).is_err());
+            continue;
+        }
+        let opcode = Opcode::try_from(M31(number)).unwrap();
+        let mut n_valid = 0;
+        for _ in 0..N_CASES {
+            let state = random_state(&mut rng);
+            let args = random_args(&mut rng);
+            let mut cells = random_cells(&mut rng);
+            // Forget the last cell that an invalid instruction accessed, which is often the one
+            // that contradicts it, until the instruction is valid.
+            let valid = loop {
+                match check(opcode, state, args, &cells) {
+                    Ok(valid) => break Some(valid),
+                    Err(Some(address)) if cells.remove(&address).is_some() => continue,
+                    Err(_) => break None,
+                }
+            };
+            let Some(valid) = valid else {
+                continue;
+            };
+            n_valid += 1;
+            // The cells after a valid instruction are consistent with it; forgetting any one of
+            // the cells it accessed makes it deduce that cell, or fail.
+            for address in valid.accessed {
+                let mut cells = valid.cells.clone();
+                cells.remove(&address);
+                let _ = check(opcode, state, args, &cells);
+            }
+            let _ = check(opcode, state, args, &valid.cells);
+        }
+        assert!(n_valid > 0, "No valid case for {opcode}.");
+    }
+}
+
+/// A hand-written instruction, run at `ap = [1:10]`, `fp = [1:8]` and `pc = [0:4]` on `cells`, with
+/// the state and the newly written cells expected after it. Negative offsets are `P - n`.
+struct Vector {
+    instruction: [u32; 4],
+    cells: Vec<(MaybeRelocatableAddr, MaybeRelocatableValue)>,
+    expected: State,
+    written: Vec<(MaybeRelocatableAddr, MaybeRelocatableValue)>,
+}
+
+fn relocatable(segment: usize, offset: u32) -> MaybeRelocatableAddr {
+    MaybeRelocatable::Relocatable((segment, offset).into())
+}
+
+fn state(ap: u32, fp: u32, pc: u32) -> State {
+    State {
+        ap: relocatable(EXECUTION, ap),
+        fp: relocatable(EXECUTION, fp),
+        pc: relocatable(PROGRAM, pc),
+    }
+}
+
+fn vectors() -> Vec<Vector> {
+    let exec = |offset| relocatable(EXECUTION, offset);
+    let felt = |value| absolute(M31(value));
+
+    vec![
+        // [ap+0] = [fp-3] + 1, ap++
+        Vector {
+            instruction: [0x1c, 0, 1, 0x7ffffffc],
+            cells: vec![(exec(5), felt(41))],
+            expected: state(11, 8, 5),
+            written: vec![(exec(10), felt(42))],
+        },
+        // [ap+0] = 7, ap++
+        Vector {
+            instruction: [0x26, 0, 7, 0],
+            cells: vec![],
+            expected: state(11, 8, 5),
+            written: vec![(exec(10), felt(7))],
+        },
+        // [ap-1] = [[fp-3]+2]
+        Vector {
+            instruction: [0x23, 0x7ffffffe, 0x7ffffffc, 2],
+            cells: vec![
+                (exec(5), relocatable(DATA, 4).into()),
+                (relocatable(DATA, 6), felt(9)),
+            ],
+            expected: state(10, 8, 5),
+            written: vec![(exec(9), felt(9))],
+        },
+        // [ap+0] = [ap-1] + [ap-2]
+        Vector {
+            instruction: [0x11, 0, 0x7ffffffe, 0x7ffffffd],
+            cells: vec![(exec(9), felt(3)), (exec(8), felt(4))],
+            expected: state(10, 8, 5),
+            written: vec![(exec(10), felt(7))],
+        },
+        // [ap-3] = [fp+0] * [fp+1], deducing [fp+1].
+        Vector {
+            instruction: [0x2d, 0x7ffffffc, 0, 1],
+            cells: vec![(exec(7), felt(12)), (exec(8), felt(3))],
+            expected: state(10, 8, 5),
+            written: vec![(exec(9), felt(4))],
+        },
+        // [fp+2] = [ap-1] * 3
+        Vector {
+            instruction: [0x51, 2, 3, 0x7ffffffe],
+            cells: vec![(exec(9), felt(5))],
+            expected: state(10, 8, 5),
+            written: vec![(exec(10), felt(15))],
+        },
+        // ap += 5
+        Vector {
+            instruction: [0x0a, 5, 0, 0],
+            cells: vec![],
+            expected: state(15, 8, 5),
+            written: vec![],
+        },
+        // ap += [fp-1] + 2
+        Vector {
+            instruction: [0x05, 2, 0x7ffffffe, 0],
+            cells: vec![(exec(7), felt(3))],
+            expected: state(15, 8, 5),
+            written: vec![],
+        },
+        // call rel 4
+        Vector {
+            instruction: [0x5a, 4, 0, 0],
+            cells: vec![],
+            expected: state(12, 12, 8),
+            written: vec![
+                (exec(10), exec(8).into()),
+                (exec(11), relocatable(PROGRAM, 5).into()),
+            ],
+        },
+        // jmp abs [fp-1]
+        Vector {
+            instruction: [0x69, 0x7ffffffe, 0, 0],
+            cells: vec![(exec(7), relocatable(PROGRAM, 6).into())],
+            expected: state(10, 8, 6),
+            written: vec![],
+        },
+        // jmp rel -1
+        Vector {
+            instruction: [0x91, 0x7ffffffe, 0, 0],
+            cells: vec![],
+            expected: state(10, 8, 3),
+            written: vec![],
+        },
+        // jmp rel 3 if [ap-1] != 0, taken and not taken.
+        Vector {
+            instruction: [0xa7, 3, 0x7ffffffe, 0],
+            cells: vec![(exec(9), felt(1))],
+            expected: state(10, 8, 7),
+            written: vec![],
+        },
+        Vector {
+            instruction: [0xa7, 3, 0x7ffffffe, 0],
+            cells: vec![(exec(9), felt(0))],
+            expected: state(10, 8, 5),
+            written: vec![],
+        },
+        // ret
+        Vector {
+            instruction: [0xab, 0, 0, 0],
+            cells: vec![
+                (exec(6), exec(2).into()),
+                (exec(7), relocatable(PROGRAM, 9).into()),
+            ],
+            expected: state(10, 2, 9),
+            written: vec![],
+        },
+    ]
+}
+
+#[test]
+fn test_instruction_vectors() {
+    for Vector {
+        instruction,
+        cells,
+        expected,
+        written,
+    } in vectors()
+    {
+        let cells: Cells = cells.into_iter().collect();
+        let mut memory = memory(&cells);
+        let decoded = DecodedInstruction::decode(Instruction::from(instruction)).unwrap();
+        let actual = (decoded.handler)(&mut memory, state(10, 8, 4), decoded.instruction.args);
+
+        assert_eq!(actual, Ok(expected), "{instruction:x?}.");
+        let mut expected_cells = cells;
+        expected_cells.extend(written);
+        for address in addresses() {
+            assert_eq!(
+                memory.get(address),
+                expected_cells.get(&address).copied(),
+                "{instruction:x?}: at {address:?}."
+            );
+        }
+    }
+}
@@ -0,0 +1,22 @@
+use bytemuck::{Pod, Zeroable};
+use serde::{Deserialize, Serialize};
+
+use crate::memory::relocatable::RelocationTable;
+use crate::vm::State;
+
+/// A single relocated trace entry.
+/// Note: This struct must be kept in sync with the prover's `vm_import::TraceEntry`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Pod, Zeroable, Deserialize, Serialize)]
+pub struct TraceEntry {
+    pub ap: u64,
+    pub fp: u64,
+    pub pc: u64,
+}
+
+impl State {
+    pub fn relocate(self, table: &RelocationTable) -> TraceEntry {
+        let [ap, fp, pc] = [self.ap, self.fp, self.pc].map(|x| u64::from(x.relocate(table).0));
+        TraceEntry { ap, fp, pc }
+    }
+}